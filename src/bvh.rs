@@ -25,7 +25,7 @@ impl Bvh {
         fn range_on_axis(world: &[Box<dyn Hit>], t_min: f64, t_max: f64, axis: usize) -> f64 {
             debug_assert!(axis <= 2);
 
-            let (min, max) = world.iter().fold((std::f64::MIN, std::f64::MAX), |range, obj| {
+            let (min, max) = world.iter().fold((f64::MIN, f64::MAX), |range, obj| {
                 if let Some(bb) = obj.bounding_box(t_min, t_max) {
                     let min = bb.min[axis].min(bb.max[axis]);
                     let max = bb.min[axis].max(bb.max[axis]);
@@ -81,7 +81,7 @@ impl Bvh {
                 );
                 let left = Box::new(
                     Bvh::new(
-                        world.drain(..).collect(),
+                        std::mem::take(&mut world),
                         t_min,
                         t_max
                     )
@@ -101,7 +101,7 @@ impl Bvh {
 }
 
 impl Hit for Bvh {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         if self.bb.hit(ray, t_min, t_max) {
             match &self.contents {
                 BvhContents::Leaf(obj) => {
@@ -136,4 +136,22 @@ impl Hit for Bvh {
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
         Some(self.bb)
     }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if !self.bb.hit(ray, t_min, t_max) {
+            return 1.0;
+        }
+
+        match &self.contents {
+            BvhContents::Leaf(obj) => obj.transmittance(ray, t_min, t_max),
+            BvhContents::Node { left, right } => {
+                let left = left.transmittance(ray, t_min, t_max);
+                if left == 0.0 {
+                    0.0
+                } else {
+                    left * right.transmittance(ray, t_min, t_max)
+                }
+            }
+        }
+    }
 }
@@ -10,6 +10,7 @@ pub struct Camera {
     lower_left_corner: Pos3,
     u: Vec3,
     v: Vec3,
+    #[allow(dead_code)]
     w: Vec3,
     lens_radius: f64,
     time_start: f64,
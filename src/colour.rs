@@ -28,6 +28,19 @@ impl Colour {
         }
     }
 
+    pub fn zip_with(self, other: Colour, mut f: impl FnMut(f64, f64) -> f64) -> Colour {
+        Colour {
+            r: f(self.r, other.r),
+            g: f(self.g, other.g),
+            b: f(self.b, other.b),
+        }
+    }
+
+    // Of linear sRGB.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn is_nan(&self) -> bool {
         self.any(f64::is_nan)
     }
@@ -61,7 +74,7 @@ impl Colour {
     }
 
     pub fn col_lerp(c1: Colour, c2: Colour, t: f64) -> Colour {
-        debug_assert!(0.0 <= t && t <= 1.0);
+        debug_assert!((0.0..=1.0).contains(&t));
     
         (1.0 - t) * c1 + t * c2
     }
@@ -3,4 +3,7 @@ pub const IMAGE_WIDTH: usize = 600;
 pub const IMAGE_HEIGHT: usize = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as usize;
 pub const SAMPLES_PER_PIXEL: usize = 200;
 pub const MAX_BOUNCES: usize = 30;
-pub const PERLIN_POINT_COUNT: usize = 256;
\ No newline at end of file
+pub const PERLIN_POINT_COUNT: usize = 256;
+pub const TILE_SIZE: usize = 32;
+pub const SEED: u64 = 1;
+pub const SAMPLES_PER_PASS: usize = 8;
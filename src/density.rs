@@ -0,0 +1,198 @@
+// Densities that vary through a volume, for media like clouds and smoke. Fields are in
+// the space of the boundary of the medium, so move with it.
+
+pub mod voxels;
+
+pub use voxels::*;
+
+use crate::aabb::*;
+use crate::colour::*;
+use crate::perlin::*;
+use crate::spectrum::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+pub type DensityField = Arc<dyn Fn(Pos3) -> f64 + Send + Sync>;
+
+// The light a glowing medium gives off, see Material::EmissiveMedium.
+pub type EmissionField = Arc<dyn Fn(Pos3) -> Colour + Send + Sync>;
+
+pub fn uniform(density: f64) -> DensityField {
+    Arc::new(
+        move |_| density
+    )
+}
+
+// Billowing noise from octaves of the absolute value of the noise, between 0 and twice
+// density but mostly below density.
+pub fn turbulence(noise: Perlin, scale: f64, density: f64) -> DensityField {
+    Arc::new(
+        move |p| {
+            let mut accum = 0.0;
+            let mut weight = 1.0;
+            let mut p = p * scale;
+            for _ in 0..5 {
+                accum += weight * noise.noise(p).abs();
+                weight *= 0.5;
+                p *= 2.0;
+            }
+            density * accum
+        }
+    )
+}
+
+// Shapes a field into a ball, taking away more of it further out, up to edge at the
+// radius, so that noise breaks up into wisps towards the outside.
+pub fn ball(field: DensityField, centre: Pos3, radius: f64, edge: f64) -> DensityField {
+    Arc::new(
+        move |p| {
+            let d2 = (p - centre).length_squared() / (radius * radius);
+            if d2 >= 1.0 {
+                0.0
+            } else {
+                (field(p) - edge * d2).max(0.0)
+            }
+        }
+    )
+}
+
+// A colour scaled by a field, e.g. glowing gas brighter where it is denser.
+pub fn glow(colour: Colour, field: DensityField) -> EmissionField {
+    Arc::new(
+        move |p| field(p) * colour
+    )
+}
+
+// Fire or anything else hot, glowing like a black body at the temperature of the field
+// in kelvin, intensity being the luminance at the reference temperature. Brightness
+// rises steeply with temperature, so cool parts don't glow visibly.
+pub fn incandescence(temperature: DensityField, reference: f64, intensity: f64) -> EmissionField {
+    let scale = intensity / blackbody_radiance(reference).luminance();
+    Arc::new(
+        move |p| {
+            let t = temperature(p);
+            if t > 0.0 {
+                scale * blackbody_radiance(t)
+            } else {
+                Colour::BLACK
+            }
+        }
+    )
+}
+
+// Values at the centres of the voxels of a box, x varying fastest, then y, then z.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    pub resolution: [usize; 3],
+    pub bounds: Aabb,
+    values: Vec<f64>,
+}
+
+impl Grid {
+    pub fn new(resolution: [usize; 3], bounds: Aabb, values: Vec<f64>) -> Grid {
+        assert_eq!(values.len(), resolution.iter().product::<usize>(), "wrong number of voxels for the resolution");
+        assert!(resolution.iter().all(|&n| n > 0), "empty grid");
+
+        Grid {
+            resolution,
+            bounds,
+            values,
+        }
+    }
+
+    // Evaluating f at the centre of each voxel.
+    pub fn from_fn(resolution: [usize; 3], bounds: Aabb, f: impl Fn(Pos3) -> f64) -> Grid {
+        let size = bounds.max - bounds.min;
+        let voxel = Vec3::new(
+            size.x / resolution[0] as f64,
+            size.y / resolution[1] as f64,
+            size.z / resolution[2] as f64,
+        );
+        let mut values = Vec::with_capacity(resolution.iter().product());
+        for z in 0..resolution[2] {
+            for y in 0..resolution[1] {
+                for x in 0..resolution[0] {
+                    let cell = Vec3::new(x as f64, y as f64, z as f64);
+                    values.push(f(bounds.min + voxel * (cell + Vec3::from(0.5))));
+                }
+            }
+        }
+
+        Grid::new(resolution, bounds, values)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values.iter().cloned().fold(0.0, f64::max)
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[x + self.resolution[0] * (y + self.resolution[1] * z)]
+    }
+
+    // Trilinearly interpolated between voxel centres, 0 outside the box.
+    pub fn at(&self, p: Pos3) -> f64 {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        if (0..3).any(|c| p[c] < min[c] || p[c] > max[c]) {
+            return 0.0;
+        }
+
+        let mut lo = [0; 3];
+        let mut hi = [0; 3];
+        let mut frac = [0.0; 3];
+        for c in 0..3 {
+            let n = self.resolution[c];
+            let x = ((p[c] - min[c]) / (max[c] - min[c]) * n as f64 - 0.5).max(0.0);
+            lo[c] = (x as usize).min(n - 1);
+            hi[c] = (lo[c] + 1).min(n - 1);
+            frac[c] = (x - lo[c] as f64).min(1.0);
+        }
+
+        let mut accum = 0.0;
+        for (k, z) in [(1.0 - frac[2], lo[2]), (frac[2], hi[2])] {
+            for (j, y) in [(1.0 - frac[1], lo[1]), (frac[1], hi[1])] {
+                for (i, x) in [(1.0 - frac[0], lo[0]), (frac[0], hi[0])] {
+                    accum += i * j * k * self.voxel(x, y, z);
+                }
+            }
+        }
+        accum
+    }
+}
+
+pub fn grid(grid: Grid) -> DensityField {
+    Arc::new(
+        move |p| grid.at(p)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grids_interpolate_trilinearly() {
+        // Voxels 1 wide, with centres at 0.5, 1.5 and so on, holding a linear function.
+        let bounds = Aabb::new(Pos3::new(0.0, 0.0, 0.0), Pos3::new(3.0, 2.0, 2.0));
+        let linear = |p: Pos3| p.x + 2.0 * p.y - 3.0 * p.z;
+        let grid = Grid::from_fn([3, 2, 2], bounds, linear);
+
+        for &p in &[Pos3::new(0.5, 0.5, 0.5), Pos3::new(1.2, 0.9, 1.3), Pos3::new(2.4, 1.5, 0.7)] {
+            assert!((grid.at(p) - linear(p)).abs() < 1e-12, "{} against {} at {:?}", grid.at(p), linear(p), p);
+        }
+
+        // Held at the outermost centres up to the edge of the box, 0 beyond it.
+        assert!((grid.at(Pos3::new(0.1, 0.2, 1.9)) - linear(Pos3::new(0.5, 0.5, 1.5))).abs() < 1e-12);
+        assert!((grid.at(Pos3::new(3.0, 2.0, 2.0)) - linear(Pos3::new(2.5, 1.5, 1.5))).abs() < 1e-12);
+        assert_eq!(grid.at(Pos3::new(3.1, 1.0, 1.0)), 0.0);
+        assert_eq!(grid.at(Pos3::new(1.0, -0.1, 1.0)), 0.0);
+
+        // Trilinear, not just linear: the product of the coordinates has a cross term.
+        let product = Grid::from_fn([2, 2, 2], bounds, |p| p.x * p.y * p.z);
+        let p = Pos3::new(1.0, 0.8, 1.1);
+        let t = |x: f64, lo: f64, hi: f64| (x - lo) / (hi - lo);
+        let (u, v, w) = (t(p.x, 0.75, 2.25), t(p.y, 0.5, 1.5), t(p.z, 0.5, 1.5));
+        let expected = (0.75 + u * 1.5) * (0.5 + v) * (0.5 + w);
+        assert!((product.at(p) - expected).abs() < 1e-12);
+    }
+}
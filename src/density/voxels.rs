@@ -0,0 +1,283 @@
+// Dense voxel volumes, e.g. simulated smoke or fire, stored as little-endian values:
+//
+//     magic        the 4 bytes "VOXL"
+//     version      u32, 1
+//     resolution   3 u32, the number of voxels along x, y and z
+//     channels     u32, flags of the optional channels: 1 temperature, 2 emission
+//     bounds       6 f32, the minimum then the maximum corner of the box the voxels fill
+//     density      an f32 per voxel
+//     temperature  an f32 per voxel, in kelvin, if flagged
+//     emission     3 f32 per voxel, red, green and blue radiance, if flagged
+//
+// Voxels go x fastest, then y, then z, each holding the value at its centre. Raw files
+// of nothing but the f32 densities in that order can be loaded too.
+
+use super::*;
+use crate::colour::*;
+use crate::hit::*;
+use crate::material::*;
+
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"VOXL";
+const VERSION: u32 = 1;
+const TEMPERATURE: u32 = 1;
+const EMISSION: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct VoxelVolume {
+    pub density: Grid,
+    pub temperature: Option<Grid>,
+    // Red, green and blue.
+    pub emission: Option<[Grid; 3]>,
+}
+
+impl VoxelVolume {
+    pub fn load(path: &Path) -> io::Result<VoxelVolume> {
+        let bytes = fs::read(path)?;
+        VoxelVolume::parse(&bytes).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    // Only densities, which the resolution and bounds aren't stored with.
+    pub fn load_raw(path: &Path, resolution: [usize; 3], bounds: Aabb) -> io::Result<VoxelVolume> {
+        let bytes = fs::read(path)?;
+        VoxelVolume::parse_raw(&bytes, resolution, bounds).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse_raw(bytes: &[u8], resolution: [usize; 3], bounds: Aabb) -> Result<VoxelVolume, String> {
+        let voxels = voxel_count(resolution, bounds)?;
+        let expected = voxels.checked_mul(4).ok_or("too many voxels")?;
+        if bytes.len() != expected {
+            return Err(format!("expected {} bytes for the resolution, found {}", expected, bytes.len()));
+        }
+        let density = Reader { bytes }.floats(voxels)?;
+
+        Ok(VoxelVolume {
+            density: Grid::new(resolution, bounds, density),
+            temperature: None,
+            emission: None,
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<VoxelVolume, String> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err("not a voxel file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported version {}", version));
+        }
+
+        let mut resolution = [0; 3];
+        for n in &mut resolution {
+            *n = reader.u32()? as usize;
+        }
+        let channels = reader.u32()?;
+
+        let corners = reader.floats(6)?;
+        let bounds = Aabb::new(
+            Pos3::new(corners[0], corners[1], corners[2]),
+            Pos3::new(corners[3], corners[4], corners[5]),
+        );
+
+        let voxels = voxel_count(resolution, bounds)?;
+        let density = Grid::new(resolution, bounds, reader.floats(voxels)?);
+        let temperature = if channels & TEMPERATURE != 0 {
+            Some(Grid::new(resolution, bounds, reader.floats(voxels)?))
+        } else {
+            None
+        };
+        let emission = if channels & EMISSION != 0 {
+            let rgb = reader.floats(voxels.checked_mul(3).ok_or("too many voxels")?)?;
+            let channel = |c: usize| Grid::new(resolution, bounds, rgb.iter().skip(c).step_by(3).cloned().collect());
+            Some([channel(0), channel(1), channel(2)])
+        } else {
+            None
+        };
+
+        if !reader.bytes.is_empty() {
+            return Err("trailing data".to_string());
+        }
+
+        Ok(VoxelVolume {
+            density,
+            temperature,
+            emission,
+        })
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.density.bounds
+    }
+
+    // Kelvin, e.g. for incandescence.
+    pub fn temperature_field(&self) -> Option<DensityField> {
+        self.temperature.clone().map(grid)
+    }
+
+    pub fn emission_field(&self) -> Option<EmissionField> {
+        self.emission.clone().map(|[r, g, b]| -> EmissionField {
+            Arc::new(
+                move |p| Colour::new(r.at(p), g.at(p), b.at(p))
+            )
+        })
+    }
+
+    // A medium filling the box of the voxels, scattering with material.
+    pub fn medium(&self, material: Material) -> HeterogeneousMedium<Cuboid> {
+        let bounds = self.bounds();
+        HeterogeneousMedium::new(
+            Cuboid::new(bounds.min, bounds.max, material.clone()),
+            grid(self.density.clone()),
+            self.density.max(),
+            material,
+        )
+    }
+}
+
+// The number of voxels of a grid, checking that it isn't empty and fits in memory.
+fn voxel_count(resolution: [usize; 3], bounds: Aabb) -> Result<usize, String> {
+    if resolution.contains(&0) {
+        return Err("empty grid".to_string());
+    }
+    if (0..3).any(|c| bounds.min[c] >= bounds.max[c] || !(bounds.max[c] - bounds.min[c]).is_finite()) {
+        return Err("empty bounds".to_string());
+    }
+
+    resolution.iter().try_fold(1usize, |n, &r| n.checked_mul(r)).ok_or_else(|| "too many voxels".to_string())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err("unexpected end of file".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn floats(&mut self, n: usize) -> Result<Vec<f64>, String> {
+        let bytes = self.take(n.checked_mul(4).ok_or("too many values")?)?;
+        let floats: Vec<f64> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64)
+            .collect();
+        if floats.iter().any(|x| !x.is_finite()) {
+            return Err("non-finite value".to_string());
+        }
+        Ok(floats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A voxel file of a 2 * 1 * 3 grid in the unit box with the given channels, each voxel
+    // of each channel holding its index.
+    fn voxel_file(channels: u32) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for v in &[VERSION, 2, 1, 3, channels] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in &[0.0f32, 0.0, 0.0, 1.0, 1.0, 1.0] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let mut values = 6;
+        if channels & TEMPERATURE != 0 {
+            values += 6;
+        }
+        if channels & EMISSION != 0 {
+            values += 18;
+        }
+        for i in 0..values {
+            bytes.extend_from_slice(&(i as f32).to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_the_flagged_channels() {
+        let volume = VoxelVolume::parse(&voxel_file(0)).unwrap();
+        assert_eq!(volume.density.resolution, [2, 1, 3]);
+        assert!(volume.temperature.is_none() && volume.emission.is_none());
+
+        let volume = VoxelVolume::parse(&voxel_file(TEMPERATURE | EMISSION)).unwrap();
+        let centre = |x: usize, z: usize| Pos3::new((x as f64 + 0.5) / 2.0, 0.5, (z as f64 + 0.5) / 3.0);
+        assert_eq!(volume.density.at(centre(1, 2)), 5.0);
+        assert_eq!(volume.temperature.as_ref().unwrap().at(centre(0, 1)), 8.0);
+        // Emission is interleaved after the temperatures, red, green and blue per voxel.
+        let [r, g, b] = volume.emission.as_ref().unwrap();
+        assert_eq!((r.at(centre(1, 0)), g.at(centre(1, 0)), b.at(centre(1, 0))), (15.0, 16.0, 17.0));
+
+        let volume = VoxelVolume::parse(&voxel_file(EMISSION)).unwrap();
+        assert!(volume.temperature.is_none() && volume.emission.is_some());
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let file = voxel_file(TEMPERATURE);
+
+        let mut bad_magic = file.clone();
+        bad_magic[0] = b'X';
+        let mut trailing = file.clone();
+        trailing.extend_from_slice(&[0; 4]);
+        let mut empty = file.clone();
+        empty[12..16].copy_from_slice(&0u32.to_le_bytes());
+        let mut unflagged = file.clone();
+        unflagged[20..24].copy_from_slice(&0u32.to_le_bytes());
+        let mut huge = file.clone();
+        for i in 0..3 {
+            huge[8 + 4 * i..12 + 4 * i].copy_from_slice(&u32::MAX.to_le_bytes());
+        }
+
+        for (bytes, error) in &[
+            (&file[..file.len() - 1], "unexpected end of file"),
+            (&file[..10], "unexpected end of file"),
+            (&bad_magic[..], "not a voxel file"),
+            (&trailing[..], "trailing data"),
+            (&empty[..], "empty grid"),
+            (&unflagged[..], "trailing data"),
+            (&huge[..], "too many voxels"),
+        ] {
+            assert_eq!(VoxelVolume::parse(bytes).unwrap_err(), *error);
+        }
+        // Without the temperatures it would be complete.
+        assert!(VoxelVolume::parse(&voxel_file(TEMPERATURE)[..file.len() - 24]).is_err());
+    }
+
+    #[test]
+    fn raw_files_are_checked_like_voxel_files() {
+        let bounds = Aabb::new(Pos3::new(0.0, 0.0, 0.0), Pos3::new(1.0, 2.0, 1.0));
+        let bytes: Vec<u8> = (0..4).flat_map(|i| (i as f32).to_le_bytes()).collect();
+
+        let volume = VoxelVolume::parse_raw(&bytes, [1, 2, 2], bounds).unwrap();
+        assert_eq!(volume.density.resolution, [1, 2, 2]);
+        assert_eq!(volume.density.at(Pos3::new(0.5, 1.5, 0.25)), 1.0);
+
+        assert_eq!(VoxelVolume::parse_raw(&bytes, [4, 0, 1], bounds).unwrap_err(), "empty grid");
+        let flat = Aabb::new(Pos3::new(0.0, 0.0, 0.0), Pos3::new(1.0, 0.0, 1.0));
+        assert_eq!(VoxelVolume::parse_raw(&bytes, [1, 2, 2], flat).unwrap_err(), "empty bounds");
+        assert_eq!(VoxelVolume::parse_raw(&bytes, [usize::MAX, 2, 1], bounds).unwrap_err(), "too many voxels");
+        assert_eq!(VoxelVolume::parse_raw(&bytes, [usize::MAX / 2, 1, 1], bounds).unwrap_err(), "too many voxels");
+        assert!(VoxelVolume::parse_raw(&bytes, [1, 2, 3], bounds).is_err());
+    }
+}
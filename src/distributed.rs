@@ -0,0 +1,391 @@
+// Splitting a render across several worker processes.
+//
+// The coordinator starts workers, either as local copies of this binary or through an
+// arbitrary command (e.g. `ssh host ray_tracing`), and talks to them over their stdin
+// and stdout. Every worker builds the same scene from the same settings and seed, so
+// the only thing that has to be sent is which pixels and samples to render:
+//
+//     render <x> <y> <width> <height> <first sample> <end sample>\n
+//
+// to which the worker answers with the resulting film in the format of `Film::write_to`.
+// Closing stdin or sending `quit` stops the worker. Since every sample is seeded from its
+// pixel and index, the merged result is the same as that of a local render.
+
+use crate::film::*;
+use crate::render::*;
+use crate::scenes::*;
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+impl Job {
+    fn as_request(&self) -> String {
+        format!(
+            "render {} {} {} {} {} {}",
+            self.rect.x, self.rect.y, self.rect.width, self.rect.height, self.samples.start, self.samples.end
+        )
+    }
+
+    fn from_request(line: &str) -> Option<Job> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("render") {
+            return None;
+        }
+
+        let nums: Vec<usize> = words.map(str::parse).collect::<Result<_, _>>().ok()?;
+        if let [x, y, width, height, start, end] = nums[..] {
+            Some(
+                Job {
+                    rect: PixelRect::new(x, y, width, height),
+                    samples: start..end,
+                }
+            )
+        } else {
+            None
+        }
+    }
+}
+
+// How to start a worker: the program followed by its arguments. The render settings
+// are appended by the coordinator.
+pub type WorkerCommand = Vec<String>;
+
+pub fn local_worker_command() -> WorkerCommand {
+    let exe = std::env::current_exe().expect("Failed finding the path of the running executable.");
+    vec![exe.to_string_lossy().into_owned()]
+}
+
+// What is left to render, shared by the threads talking to the workers.
+struct Queue<I: Iterator<Item = Job>> {
+    jobs: I,
+    // Jobs given back by failed workers, handed out before any new ones.
+    retries: VecDeque<Job>,
+    // Jobs that workers are rendering, any of which might fail and come back.
+    in_flight: usize,
+    done: usize,
+}
+
+// The two ends of the connection to a worker, and its process if it has one.
+struct Connection {
+    input: Box<dyn Write + Send>,
+    output: Box<dyn BufRead + Send>,
+    child: Option<Child>,
+}
+
+// Hands out jobs to the workers until all are rendered or the render is stopped, and
+// merges the results. Jobs of a worker that fails are given to the remaining workers.
+pub fn render_distributed(
+    settings: &RenderSettings,
+    workers: &[WorkerCommand],
+    worker_args: &[String],
+    cancel: &CancelToken,
+) -> io::Result<Film> {
+    let connect: Vec<_> = workers
+        .iter()
+        .map(|command| move || {
+            let mut child = spawn_worker(command, worker_args)?;
+            Ok(Connection {
+                input: Box::new(BufWriter::new(child.stdin.take().unwrap())),
+                output: Box::new(BufReader::new(child.stdout.take().unwrap())),
+                child: Some(child),
+            })
+        })
+        .collect();
+
+    coordinate(settings, &connect, cancel)
+}
+
+fn coordinate(
+    settings: &RenderSettings,
+    workers: &[impl Fn() -> io::Result<Connection> + Sync],
+    cancel: &CancelToken,
+) -> io::Result<Film> {
+    let stop = StopCondition::new(settings, cancel);
+    let num_jobs = settings.num_jobs();
+    let queue = Mutex::new(Queue {
+        jobs: settings.passes().flatten(),
+        retries: VecDeque::new(),
+        in_flight: 0,
+        done: 0,
+    });
+    // Signalled whenever a job finishes or comes back.
+    let changed = Condvar::new();
+    let film = Mutex::new(Film::new(settings.crop_rect()));
+
+    thread::scope(|s| {
+        for (i, connect) in workers.iter().enumerate() {
+            let (queue, changed, film, stop) = (&queue, &changed, &film, &stop);
+            s.spawn(move || {
+                let Connection { mut input, mut output, child } = match connect() {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        eprintln!("Failed starting worker {}: {}", i, e);
+                        return;
+                    }
+                };
+
+                loop {
+                    // Once nothing is left to hand out, wait in case a job that is still
+                    // being rendered fails and comes back.
+                    let job = {
+                        let mut queue = queue.lock().unwrap();
+                        loop {
+                            if stop.should_stop() {
+                                break None;
+                            }
+                            let next = queue.retries.pop_front().or_else(|| queue.jobs.next());
+                            if let Some(job) = next {
+                                queue.in_flight += 1;
+                                break Some(job);
+                            }
+                            if queue.in_flight == 0 {
+                                break None;
+                            }
+                            queue = changed.wait_timeout(queue, Duration::from_millis(100)).unwrap().0;
+                        }
+                    };
+                    let job = match job {
+                        Some(job) => job,
+                        None => break,
+                    };
+
+                    // A film for other pixels than asked for means the worker is broken
+                    // or out of step, so it is treated as failing.
+                    let expected = job.rect.expand(settings.filter.margin(), &settings.full_frame());
+                    let result = writeln!(input, "{}", job.as_request())
+                        .and_then(|_| input.flush())
+                        .and_then(|_| Film::read_from(&mut output))
+                        .and_then(|tile_film| {
+                            if tile_film.rect == expected {
+                                Ok(tile_film)
+                            } else {
+                                Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    format!("film of {:?} for the tile {:?}", tile_film.rect, job.rect),
+                                ))
+                            }
+                        });
+
+                    let failed = result.is_err();
+                    match result {
+                        Ok(tile_film) => {
+                            film.lock().unwrap().merge(&tile_film);
+                            let mut queue = queue.lock().unwrap();
+                            queue.in_flight -= 1;
+                            queue.done += 1;
+                            eprintln!("Worker {} finished tile ({}/{})", i, queue.done, num_jobs);
+                        },
+                        Err(e) => {
+                            eprintln!("Worker {} failed: {}", i, e);
+                            let mut queue = queue.lock().unwrap();
+                            queue.in_flight -= 1;
+                            queue.retries.push_back(job);
+                        }
+                    }
+                    changed.notify_all();
+                    if failed {
+                        break;
+                    }
+                }
+
+                let _ = writeln!(input, "quit").and_then(|_| input.flush());
+                drop(input);
+                if let Some(mut child) = child {
+                    let _ = child.wait();
+                }
+            });
+        }
+    });
+
+    let remaining = num_jobs - queue.into_inner().unwrap().done;
+    if remaining > 0 && stop.should_stop() {
+        eprintln!("Stopped early, {} of {} tiles rendered.", num_jobs - remaining, num_jobs);
+    } else if remaining > 0 {
+        return Err(io::Error::other(format!("All workers failed with {} tiles left to render.", remaining)));
+    }
+
+    Ok(settings.output_film(film.into_inner().unwrap()))
+}
+
+fn spawn_worker(command: &[String], worker_args: &[String]) -> io::Result<Child> {
+    let (program, args) = command.split_first().expect("Empty worker command.");
+
+    Command::new(program)
+        .args(args)
+        .args(worker_args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+}
+
+// The worker side of the protocol, rendering jobs from stdin until it's closed.
+pub fn run_worker(scene: &Scene, settings: &RenderSettings) -> io::Result<()> {
+    let stdout = io::stdout();
+    serve(scene, settings, io::stdin().lock(), &mut BufWriter::new(stdout.lock()))
+}
+
+fn serve(scene: &Scene, settings: &RenderSettings, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+
+        let job = Job::from_request(&line).ok_or_else(||
+            io::Error::new(io::ErrorKind::InvalidData, format!("Malformed request: {:?}", line))
+        )?;
+
+        render_region(scene, settings, job.rect, job.samples).write_to(output)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::pipe;
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 24,
+            height: 16,
+            samples_per_pixel: 4,
+            samples_per_pass: 2,
+            max_bounces: 4,
+            tile_size: 8,
+            seed: 7,
+            ..RenderSettings::default()
+        }
+    }
+
+    // A worker on a thread of this process, talking over pipes like a real one. With
+    // fail_after it answers that many requests, then reads one more and dies without
+    // answering, a while later so that the other workers have run out of jobs.
+    fn thread_worker<'s>(
+        s: &'s thread::Scope<'s, '_>,
+        scene: &'s Scene,
+        settings: &'s RenderSettings,
+        fail_after: Option<usize>,
+    ) -> io::Result<Connection> {
+        let (requests, request_input) = pipe()?;
+        let (film_output, films) = pipe()?;
+
+        s.spawn(move || {
+            let (requests, mut films) = (BufReader::new(requests), films);
+            match fail_after {
+                None => serve(scene, settings, requests, &mut films).unwrap(),
+                Some(n) => {
+                    let mut lines = requests.lines();
+                    for line in lines.by_ref().take(n) {
+                        let job = Job::from_request(&line.unwrap()).unwrap();
+                        render_region(scene, settings, job.rect, job.samples).write_to(&mut films).unwrap();
+                    }
+                    let _ = lines.next();
+                    thread::sleep(Duration::from_millis(300));
+                }
+            }
+        });
+
+        Ok(Connection {
+            input: Box::new(request_input),
+            output: Box::new(BufReader::new(film_output)),
+            child: None,
+        })
+    }
+
+    // A worker that answers every request with a film of the pixels to the right of those
+    // asked for.
+    fn misplaced_worker<'s>(s: &'s thread::Scope<'s, '_>, scene: &'s Scene, settings: &'s RenderSettings) -> io::Result<Connection> {
+        let (requests, request_input) = pipe()?;
+        let (film_output, films) = pipe()?;
+
+        s.spawn(move || {
+            let mut films = films;
+            for line in BufReader::new(requests).lines() {
+                let job = match Job::from_request(&line.unwrap()) {
+                    Some(job) => job,
+                    None => break,
+                };
+                let rect = PixelRect::new(job.rect.x + 1, job.rect.y, job.rect.width, job.rect.height);
+                if render_region(scene, settings, rect, job.samples).write_to(&mut films).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Connection {
+            input: Box::new(request_input),
+            output: Box::new(BufReader::new(film_output)),
+            child: None,
+        })
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let job = Job {
+            rect: PixelRect::new(16, 32, 8, 5),
+            samples: 4..12,
+        };
+        assert_eq!(Job::from_request(&job.as_request()), Some(job));
+
+        assert_eq!(Job::from_request("render 1 2 3 4 5"), None);
+        assert_eq!(Job::from_request("render 1 2 3 4 5 6 7"), None);
+        assert_eq!(Job::from_request("render 1 2 3 4 5 -6"), None);
+        assert_eq!(Job::from_request("quit"), None);
+    }
+
+    #[test]
+    fn failed_jobs_go_to_the_other_workers() {
+        let settings = settings();
+        let scene = build_scene("three_different_objects", settings.seed).unwrap();
+        let local = render(&scene, &settings, &CancelToken::new());
+
+        let distributed = thread::scope(|s| {
+            let (scene, settings) = (&scene, &settings);
+            let workers = [Some(1), None, Some(0)].map(|fail_after| move || thread_worker(s, scene, settings, fail_after));
+            coordinate(settings, &workers, &CancelToken::new())
+        }).unwrap();
+
+        assert_eq!(distributed.rect, local.rect);
+        assert_same_pixels(&distributed, &local, local.rect);
+    }
+
+    #[test]
+    fn films_of_the_wrong_pixels_are_rendered_again() {
+        let settings = settings();
+        let scene = build_scene("three_different_objects", settings.seed).unwrap();
+        let local = render(&scene, &settings, &CancelToken::new());
+
+        let distributed = thread::scope(|s| {
+            let (scene, settings) = (&scene, &settings);
+            let workers: [Box<dyn Fn() -> io::Result<Connection> + Sync>; 2] = [
+                Box::new(move || misplaced_worker(s, scene, settings)),
+                Box::new(move || thread_worker(s, scene, settings, None)),
+            ];
+            coordinate(settings, &workers, &CancelToken::new())
+        }).unwrap();
+
+        assert_same_pixels(&distributed, &local, local.rect);
+    }
+
+    #[test]
+    fn fails_when_every_worker_does() {
+        let settings = settings();
+        let scene = build_scene("three_different_objects", settings.seed).unwrap();
+
+        let result = thread::scope(|s| {
+            let (scene, settings) = (&scene, &settings);
+            let workers = [Some(2), Some(0)].map(|fail_after| move || thread_worker(s, scene, settings, fail_after));
+            coordinate(settings, &workers, &CancelToken::new())
+        });
+
+        assert!(result.is_err());
+    }
+}
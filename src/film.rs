@@ -0,0 +1,284 @@
+use crate::colour::*;
+use crate::filter::*;
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+// A rectangle of pixels, with (x, y) being the top left corner and y growing downwards.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PixelRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PixelRect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> PixelRect {
+        PixelRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        self.x <= x && x < self.x.saturating_add(self.width) && self.y <= y && y < self.y.saturating_add(self.height)
+    }
+
+    pub fn intersect(&self, other: &PixelRect) -> Option<PixelRect> {
+        let x0 = self.x.max(other.x);
+        let y0 = self.y.max(other.y);
+        // Saturating, as a crop window from the command line can be anywhere.
+        let x1 = self.x.saturating_add(self.width).min(other.x.saturating_add(other.width));
+        let y1 = self.y.saturating_add(self.height).min(other.y.saturating_add(other.height));
+
+        if x0 < x1 && y0 < y1 {
+            Some(PixelRect::new(x0, y0, x1 - x0, y1 - y0))
+        } else {
+            None
+        }
+    }
+
+    // The rectangle grown by margin pixels on every side, limited to bounds.
+    pub fn expand(&self, margin: usize, bounds: &PixelRect) -> PixelRect {
+        let x0 = self.x.saturating_sub(margin);
+        let y0 = self.y.saturating_sub(margin);
+        let x1 = self.x.saturating_add(self.width).saturating_add(margin);
+        let y1 = self.y.saturating_add(self.height).saturating_add(margin);
+        let grown = PixelRect::new(x0, y0, x1 - x0, y1 - y0);
+        grown.intersect(bounds).unwrap_or(*self)
+    }
+
+    // Splits the rectangle into tiles of at most tile_size * tile_size pixels, row by row.
+    pub fn tiles(&self, tile_size: usize) -> Vec<PixelRect> {
+        debug_assert!(tile_size > 0);
+        let mut tiles = Vec::new();
+
+        for y in (self.y..self.y + self.height).step_by(tile_size) {
+            for x in (self.x..self.x + self.width).step_by(tile_size) {
+                tiles.push(
+                    PixelRect::new(
+                        x,
+                        y,
+                        tile_size.min(self.x + self.width - x),
+                        tile_size.min(self.y + self.height - y),
+                    )
+                );
+            }
+        }
+
+        tiles
+    }
+}
+
+// The most pixels a film read from elsewhere can have, those of a 16384 * 16384 image.
+pub const MAX_FILM_PIXELS: usize = 1 << 28;
+
+// Accumulates weighted colour samples for a rectangle of pixels. The pixel coordinates
+// are always those of the full image, so films for different parts of an image can be
+// merged without any extra bookkeeping.
+#[derive(Debug, Clone)]
+pub struct Film {
+    pub rect: PixelRect,
+    sums: Vec<Colour>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(rect: PixelRect) -> Film {
+        Film {
+            rect,
+            sums: vec![Colour::BLACK; rect.area()],
+            weights: vec![0.0; rect.area()],
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        debug_assert!(self.rect.contains(x, y));
+        (y - self.rect.y) * self.rect.width + (x - self.rect.x)
+    }
+
+    pub fn add_sample(&mut self, x: usize, y: usize, col: Colour, weight: f64) {
+        debug_assert!(!col.is_nan());
+        let i = self.index(x, y);
+        self.sums[i] += weight * col;
+        self.weights[i] += weight;
+    }
+
+    // Adds a sample at the continuous position (x, y), where pixel (i, j) covers
+    // [i, i + 1) * [j, j + 1), to every pixel of the film within reach of the filter.
+    // Like the pixels, the reach is half open, so a sample on the edge of a pixel doesn't
+    // spill into the one before it, beyond the margin of the filter.
+    pub fn splat(&mut self, x: f64, y: f64, col: Colour, filter: &Filter) {
+        let radius = filter.radius();
+        let range = |p: f64, start: usize, len: usize| {
+            let first = ((p - 0.5 - radius).floor() + 1.0).max(start as f64) as usize;
+            let last = ((p - 0.5 + radius).floor() + 1.0).clamp(0.0, (start + len) as f64) as usize;
+            first..last
+        };
+
+        for j in range(y, self.rect.y, self.rect.height) {
+            for i in range(x, self.rect.x, self.rect.width) {
+                let weight = filter.eval(i as f64 + 0.5 - x, j as f64 + 0.5 - y);
+                if weight != 0.0 {
+                    self.add_sample(i, j, col, weight);
+                }
+            }
+        }
+    }
+
+    // Adds all samples of other that fall within this film.
+    pub fn merge(&mut self, other: &Film) {
+        if let Some(overlap) = self.rect.intersect(&other.rect) {
+            for y in overlap.y..overlap.y + overlap.height {
+                for x in overlap.x..overlap.x + overlap.width {
+                    let (i, j) = (self.index(x, y), other.index(x, y));
+                    self.sums[i] += other.sums[j];
+                    self.weights[i] += other.weights[j];
+                }
+            }
+        }
+    }
+
+    pub fn weight(&self, x: usize, y: usize) -> f64 {
+        self.weights[self.index(x, y)]
+    }
+
+    // The weighted average of all samples in a pixel, black if it has none. Filters
+    // with negative lobes can give negative averages, which are clamped to zero.
+    pub fn pixel(&self, x: usize, y: usize) -> Colour {
+        let i = self.index(x, y);
+        if self.weights[i] > 0.0 {
+            (self.sums[i] / self.weights[i]).map(|c| c.max(0.0))
+        } else {
+            Colour::BLACK
+        }
+    }
+
+    // Binary format: x, y, width and height as little endian u64s, followed by
+    // r, g, b and weight as little endian f64s for every pixel, row by row.
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let rect = self.rect;
+        for v in &[rect.x, rect.y, rect.width, rect.height] {
+            out.write_all(&(*v as u64).to_le_bytes())?;
+        }
+
+        let mut buf = Vec::with_capacity(rect.area() * 4 * 8);
+        for (sum, weight) in self.sums.iter().zip(&self.weights) {
+            for v in &[sum.r, sum.g, sum.b, *weight] {
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        out.write_all(&buf)?;
+        out.flush()
+    }
+
+    pub fn read_from(input: &mut impl Read) -> io::Result<Film> {
+        fn read_8(input: &mut impl Read) -> io::Result<[u8; 8]> {
+            let mut buf = [0; 8];
+            input.read_exact(&mut buf)?;
+            Ok(buf)
+        }
+
+        let mut header = [0usize; 4];
+        for v in header.iter_mut() {
+            *v = usize::try_from(u64::from_le_bytes(read_8(input)?)).unwrap_or(usize::MAX);
+        }
+        // The film comes from another process, so a broken header mustn't overflow or
+        // allocate more than any image could need.
+        let [x, y, width, height] = header;
+        let area = width.checked_mul(height).filter(|&area| area <= MAX_FILM_PIXELS);
+        if area.is_none() || x.checked_add(width).is_none() || y.checked_add(height).is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid film rect {:?}", header)));
+        }
+        let mut film = Film::new(PixelRect::new(x, y, width, height));
+
+        for i in 0..film.rect.area() {
+            let mut vals = [0.0; 4];
+            for v in vals.iter_mut() {
+                *v = f64::from_le_bytes(read_8(input)?);
+            }
+            film.sums[i] = Colour::new(vals[0], vals[1], vals[2]);
+            film.weights[i] = vals[3];
+        }
+
+        Ok(film)
+    }
+}
+
+// Asserts that two films have the same pixels within rect, up to the rounding of adding
+// up their samples in a different order.
+#[cfg(test)]
+pub fn assert_same_pixels(a: &Film, b: &Film, rect: PixelRect) {
+    for y in rect.y..rect.y + rect.height {
+        for x in rect.x..rect.x + rect.width {
+            let (pa, pb) = (a.pixel(x, y), b.pixel(x, y));
+            let close = |u: f64, v: f64| (u - v).abs() <= 1e-9 * (1.0 + u.abs().max(v.abs()));
+            assert!(
+                close(pa.r, pb.r) && close(pa.g, pb.g) && close(pa.b, pb.b) && close(a.weight(x, y), b.weight(x, y)),
+                "pixel ({}, {}) differs: {:?} against {:?}", x, y, pa, pb,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn film_round_trips() {
+        let mut film = Film::new(PixelRect::new(3, 5, 4, 2));
+        film.add_sample(3, 5, Colour::new(0.25, 1.5, -0.5), 0.75);
+        film.add_sample(6, 6, Colour::new(1e-12, 3.0, 7.0), 2.0);
+
+        let mut bytes = Vec::new();
+        film.write_to(&mut bytes).unwrap();
+        let read = Film::read_from(&mut &bytes[..]).unwrap();
+
+        assert_eq!(read.rect, film.rect);
+        assert_eq!(read.sums.len(), film.sums.len());
+        for i in 0..film.sums.len() {
+            assert_eq!((read.sums[i].r, read.sums[i].g, read.sums[i].b), (film.sums[i].r, film.sums[i].g, film.sums[i].b));
+            assert_eq!(read.weights[i], film.weights[i]);
+        }
+        assert!(Film::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn broken_headers_are_rejected() {
+        let header = |values: [u64; 4]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+
+        for values in [
+            [0, 0, u64::MAX, 2],
+            [0, 0, 1 << 32, 1 << 32],
+            [0, 0, 1 << 15, 1 << 15],
+            [u64::MAX, 0, 2, 2],
+            [0, u64::MAX - 1, 1, 2],
+        ] {
+            let err = Film::read_from(&mut &header(values)[..]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", values);
+        }
+        // A valid header, with the pixels missing.
+        let err = Film::read_from(&mut &header([0, 0, 2, 2])[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rects_far_out_do_not_overflow() {
+        let image = PixelRect::new(0, 0, 64, 48);
+        let far = PixelRect::new(usize::MAX - 1, 10, usize::MAX, usize::MAX);
+
+        assert_eq!(far.intersect(&image), None);
+        assert_eq!(image.intersect(&far), None);
+        assert_eq!(PixelRect::new(60, 40, usize::MAX, usize::MAX).intersect(&image), Some(PixelRect::new(60, 40, 4, 8)));
+        assert!(far.contains(usize::MAX - 1, usize::MAX - 1));
+        assert_eq!(PixelRect::new(60, 40, usize::MAX, 4).expand(2, &image), PixelRect::new(58, 38, 6, 8));
+    }
+}
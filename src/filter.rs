@@ -0,0 +1,225 @@
+use crate::utility::*;
+
+// Pixel reconstruction filters. Every sample is splatted into all pixels whose centres
+// lie within the radius of the filter, weighted by the filter, and pixels are then
+// normalised by their accumulated weight. All filters are separable.
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    Box {
+        radius: f64,
+    },
+    Tent {
+        radius: f64,
+    },
+    Gaussian {
+        radius: f64,
+        alpha: f64,
+    },
+    // b and c as in the paper, 1/3 each is the recommended choice.
+    MitchellNetravali {
+        radius: f64,
+        b: f64,
+        c: f64,
+    },
+    // A sinc windowed by a sinc with as many lobes as the radius.
+    Lanczos {
+        radius: f64,
+    },
+}
+
+impl Filter {
+    // A filter with sensible default parameters, by name.
+    pub fn from_name(name: &str, radius: Option<f64>) -> Option<Filter> {
+        let filter = match name {
+            "box" => Filter::Box { radius: radius.unwrap_or(0.5) },
+            "tent" => Filter::Tent { radius: radius.unwrap_or(1.0) },
+            "gaussian" => Filter::Gaussian { radius: radius.unwrap_or(1.5), alpha: 2.0 },
+            "mitchell" => Filter::MitchellNetravali { radius: radius.unwrap_or(2.0), b: 1.0 / 3.0, c: 1.0 / 3.0 },
+            "lanczos" => Filter::Lanczos { radius: radius.unwrap_or(3.0) },
+            _ => return None,
+        };
+
+        Some(filter)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Box { .. } => "box",
+            Filter::Tent { .. } => "tent",
+            Filter::Gaussian { .. } => "gaussian",
+            Filter::MitchellNetravali { .. } => "mitchell",
+            Filter::Lanczos { .. } => "lanczos",
+        }
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::MitchellNetravali { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    // How many pixels beyond its own a sample can reach.
+    pub fn margin(&self) -> usize {
+        (self.radius() - 0.5).ceil().max(0.0) as usize
+    }
+
+    // The weight of a sample at offset (dx, dy) from a pixel centre.
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    fn eval_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            },
+            Filter::MitchellNetravali { radius, b, c } => {
+                // The filter is defined on [-2, 2].
+                let x = 2.0 * x / radius;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b)) / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)) / 6.0
+                }
+            },
+            Filter::Lanczos { radius } => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+impl Default for Filter {
+    // A box covering exactly one pixel, i.e. a plain average of the samples in a pixel.
+    fn default() -> Filter {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::*;
+    use crate::film::*;
+
+    fn all_filters() -> Vec<Filter> {
+        ["box", "tent", "gaussian", "mitchell", "lanczos"]
+            .iter()
+            .flat_map(|name| [None, Some(0.8), Some(2.5)].map(|radius| Filter::from_name(name, radius).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn weights_are_zero_outside_the_radius() {
+        for filter in all_filters() {
+            let radius = filter.radius();
+            assert!(filter.eval(0.0, 0.0) > 0.0, "{:?}", filter);
+            for i in 0..=100 {
+                let x = 3.0 * radius * i as f64 / 100.0;
+                assert_eq!(filter.eval(x, 0.1), filter.eval(-x, -0.1), "{:?} isn't symmetric", filter);
+                if x > radius {
+                    assert_eq!(filter.eval(x, 0.0), 0.0, "{:?} reaches {}", filter, x);
+                    assert_eq!(filter.eval(0.0, x), 0.0, "{:?} reaches {}", filter, x);
+                }
+            }
+        }
+    }
+
+    // Splatting reaches every pixel whose centre is within the radius, and no pixel
+    // further out than the margin, even from the very edge of a pixel.
+    #[test]
+    fn splats_stay_within_the_margin() {
+        for filter in all_filters() {
+            let (radius, margin) = (filter.radius(), filter.margin());
+            for &(x, y) in &[(10.0, 10.0), (10.5, 10.5), (10.999, 10.25), (10.01, 10.99)] {
+                let mut film = Film::new(PixelRect::new(0, 0, 21, 21));
+                film.splat(x, y, Colour::WHITE, &filter);
+
+                for j in 0..21 {
+                    for i in 0..21 {
+                        let (dx, dy) = (i as f64 + 0.5 - x, j as f64 + 0.5 - y);
+                        let weight = film.weight(i, j);
+                        let expected = if dx > -radius && dy > -radius { filter.eval(dx, dy) } else { 0.0 };
+                        assert_eq!(weight, expected, "{:?} at ({}, {})", filter, i, j);
+                        if weight != 0.0 {
+                            assert!(dx.abs() <= radius && dy.abs() <= radius);
+                            assert!(i.max(10) - i.min(10) <= margin && j.max(10) - j.min(10) <= margin);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Pixels are divided by the weights of their samples, so whatever the filter, an
+    // evenly lit image comes out at exactly that colour.
+    #[test]
+    fn pixels_are_normalised() {
+        let colour = Colour::new(0.2, 0.7, 3.0);
+        for filter in all_filters() {
+            let mut film = Film::new(PixelRect::new(0, 0, 16, 16));
+            for j in 0..16 * 4 {
+                for i in 0..16 * 4 {
+                    film.splat((i as f64 + 0.5) / 4.0, (j as f64 + 0.5) / 4.0, colour, &filter);
+                }
+            }
+
+            let (lo, hi) = (filter.margin(), 16 - filter.margin());
+            for y in lo..hi {
+                for x in lo..hi {
+                    let pixel = film.pixel(x, y);
+                    assert!(film.weight(x, y) > 0.0);
+                    assert!((pixel.r - colour.r).abs() < 1e-9 && (pixel.g - colour.g).abs() < 1e-9 && (pixel.b - colour.b).abs() < 1e-9, "{:?} gives {:?}", filter, pixel);
+                }
+            }
+        }
+    }
+
+    // The negative lobes of Mitchell-Netravali and Lanczos can leave pixels next to a
+    // bright sample with a negative sum or weight, which mustn't show up as negative or
+    // NaN pixels.
+    #[test]
+    fn negative_lobes_give_valid_pixels() {
+        for filter in all_filters() {
+            for seed in 0..20 {
+                seed_rng(seed);
+                let mut film = Film::new(PixelRect::new(0, 0, 12, 12));
+                for _ in 0..random_in_range(1.0, 200.0) as usize {
+                    let bright = if random_zero_one() < 0.1 { 1000.0 } else { 0.0 };
+                    let colour = Colour::new(bright, random_zero_one(), 0.0);
+                    film.splat(random_in_range(0.0, 12.0), random_in_range(0.0, 12.0), colour, &filter);
+                }
+
+                for y in 0..12 {
+                    for x in 0..12 {
+                        let pixel = film.pixel(x, y);
+                        assert!(!pixel.is_nan() && pixel.r.is_finite(), "{:?} gives {:?}", filter, pixel);
+                        assert!(pixel.r >= 0.0 && pixel.g >= 0.0 && pixel.b >= 0.0, "{:?} gives {:?}", filter, pixel);
+                    }
+                }
+            }
+        }
+    }
+}
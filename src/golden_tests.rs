@@ -0,0 +1,273 @@
+// Renders every built-in scene at a low resolution with a fixed seed and compares it to
+// a stored reference image, so that changes to e.g. the BVH, materials or transforms
+// can't silently change the output. They only tell that something changed, not whether
+// it is right, which is up to the unit tests of each module.
+//
+// Run with UPDATE_GOLDEN=1 to write new reference images after an intended change.
+// A failing test writes the render and a difference image next to each other in
+// target/golden.
+
+use crate::film::*;
+use crate::image::*;
+use crate::render::*;
+use crate::scenes::*;
+
+use ::image::{Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+
+const GOLDEN_SIZE: usize = 48;
+const GOLDEN_SAMPLES: usize = 8;
+const GOLDEN_BOUNCES: usize = 10;
+const GOLDEN_SEED: u64 = 42;
+// Lets through small differences, e.g. from floating point differences between
+// platforms, but not changes in what is rendered.
+const MIN_PSNR: f64 = 40.0;
+
+fn golden_settings() -> RenderSettings {
+    RenderSettings {
+        width: GOLDEN_SIZE,
+        height: GOLDEN_SIZE,
+        samples_per_pixel: GOLDEN_SAMPLES,
+        max_bounces: GOLDEN_BOUNCES,
+        seed: GOLDEN_SEED,
+        ..RenderSettings::default()
+    }
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new("assets/golden").join(format!("{}.png", name))
+}
+
+fn failure_path(name: &str, kind: &str) -> PathBuf {
+    Path::new("target/golden").join(format!("{}_{}.png", name, kind))
+}
+
+// Root mean square error over all channels, in [0, 1].
+fn rmse(a: &RgbImage, b: &RgbImage) -> f64 {
+    let sum: f64 = a.iter()
+        .zip(b.iter())
+        .map(|(&a, &b)| ((a as f64 - b as f64) / 255.0).powi(2))
+        .sum();
+
+    (sum / a.len() as f64).sqrt()
+}
+
+fn psnr(rmse: f64) -> f64 {
+    if rmse == 0.0 {
+        f64::INFINITY
+    } else {
+        20.0 * (1.0 / rmse).log10()
+    }
+}
+
+// The absolute difference per channel, scaled up to make small differences visible.
+fn diff_image(a: &RgbImage, b: &RgbImage) -> RgbImage {
+    RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let mut diff = [0; 3];
+        for c in 0..3 {
+            diff[c] = ((pa[c] as i32 - pb[c] as i32).abs() * 4).min(255) as u8;
+        }
+        Rgb(diff)
+    })
+}
+
+fn render_scene(name: &str) -> RgbImage {
+    let settings = golden_settings();
+    let scene = build_scene(name, settings.seed).unwrap();
+    let film: Film = render(&scene, &settings, &CancelToken::new());
+
+    Image::from_film(&film).to_rgb_image()
+}
+
+fn check_scene(name: &str) {
+    let actual = render_scene(name);
+    let reference_path = reference_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = match ::image::open(&reference_path) {
+        Ok(reference) => reference.to_rgb(),
+        Err(e) => panic!("Failed opening {:?} ({}), run with UPDATE_GOLDEN=1 to create it.", reference_path, e),
+    };
+    assert_eq!(reference.dimensions(), actual.dimensions(), "Reference image of {} has the wrong size.", name);
+
+    let rmse = rmse(&actual, &reference);
+    let psnr = psnr(rmse);
+    if psnr < MIN_PSNR {
+        std::fs::create_dir_all("target/golden").unwrap();
+        actual.save(failure_path(name, "actual")).unwrap();
+        diff_image(&actual, &reference).save(failure_path(name, "diff")).unwrap();
+
+        panic!(
+            "{} differs from its reference image: RMSE {:.4}, PSNR {:.1} dB (minimum {} dB). See {:?}.",
+            name, rmse, psnr, MIN_PSNR, failure_path(name, "diff")
+        );
+    }
+}
+
+#[test]
+fn all_scenes_are_tested() {
+    for name in SCENE_NAMES.iter() {
+        assert!(reference_path(name).exists() || std::env::var_os("UPDATE_GOLDEN").is_some(), "No reference image for {}.", name);
+    }
+}
+
+#[test]
+fn rendering_is_deterministic() {
+    assert_eq!(render_scene("cornell_box").into_raw(), render_scene("cornell_box").into_raw());
+}
+
+#[test]
+fn final_scene_2() {
+    check_scene("final_scene_2");
+}
+
+#[test]
+fn cornell_box_smoke() {
+    check_scene("cornell_box_smoke");
+}
+
+#[test]
+fn cornell_box() {
+    check_scene("cornell_box");
+}
+
+#[test]
+fn rectangle_light_test() {
+    check_scene("rectangle_light_test");
+}
+
+#[test]
+fn texture_test() {
+    check_scene("texture_test");
+}
+
+#[test]
+fn perlin_test() {
+    check_scene("perlin_test");
+}
+
+#[test]
+fn final_scene_1() {
+    check_scene("final_scene_1");
+}
+
+#[test]
+fn test_bvh() {
+    check_scene("test_bvh");
+}
+
+#[test]
+fn three_different_objects() {
+    check_scene("three_different_objects");
+}
+
+#[test]
+fn two_touching_objects() {
+    check_scene("two_touching_objects");
+}
+
+#[test]
+fn metals() {
+    check_scene("metals");
+}
+
+#[test]
+fn frosted_glass() {
+    check_scene("frosted_glass");
+}
+
+#[test]
+fn coloured_glass() {
+    check_scene("coloured_glass");
+}
+
+#[test]
+fn dispersive_glass() {
+    check_scene("dispersive_glass");
+}
+
+#[test]
+fn principled() {
+    check_scene("principled");
+}
+
+#[test]
+fn layered() {
+    check_scene("layered");
+}
+
+#[test]
+fn rough_diffuse() {
+    check_scene("rough_diffuse");
+}
+
+#[test]
+fn mix() {
+    check_scene("mix");
+}
+
+#[test]
+fn textured_materials() {
+    check_scene("textured_materials");
+}
+
+#[test]
+fn bump_mapping() {
+    check_scene("bump_mapping");
+}
+
+#[test]
+fn subsurface() {
+    check_scene("subsurface");
+}
+
+#[test]
+fn thin_film_and_sheen() {
+    check_scene("thin_film_and_sheen");
+}
+
+#[test]
+fn emitters() {
+    check_scene("emitters");
+}
+
+#[test]
+fn delta_lights() {
+    check_scene("delta_lights");
+}
+
+#[test]
+fn ies_lights() {
+    check_scene("ies_lights");
+}
+
+#[test]
+fn many_lights() {
+    check_scene("many_lights");
+}
+
+#[test]
+fn forward_scattering() {
+    check_scene("forward_scattering");
+}
+
+#[test]
+fn clouds() {
+    check_scene("clouds");
+}
+
+#[test]
+fn voxel_smoke() {
+    check_scene("voxel_smoke");
+}
+
+#[test]
+fn fire() {
+    check_scene("fire");
+}
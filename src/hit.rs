@@ -12,12 +12,14 @@ pub mod rect;
 pub mod sphere;
 pub mod transforms;
 pub mod constant_medium;
+pub mod heterogeneous_medium;
 
 pub use cuboid::*;
 pub use rect::*;
 pub use sphere::*;
 pub use transforms::*;
 pub use constant_medium::*;
+pub use heterogeneous_medium::*;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Side {
@@ -39,17 +41,24 @@ impl Not for Side {
 #[derive(Clone, Copy)]
 pub struct HitRecord<'m> {
     pub p: Pos3,
+    // p in the space of the object hit, before any transforms of it, e.g. for the fields
+    // of a medium that move with it.
+    pub local: Pos3,
     pub normal: Vec3,
     pub t: f64,
     pub u: f64,
     pub v: f64,
     pub side: Side,
     pub material: &'m Material,
+    // How p changes with u and v, giving the tangents of the surface for bump and normal
+    // mapping.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
 
 impl HitRecord<'_> {
     pub fn face(normal: &Vec3, ray: &Ray) -> (Vec3, Side) {
-        if Vec3::dot(&ray.direction, &normal) > 0.0 {
+        if Vec3::dot(&ray.direction, normal) > 0.0 {
             (-*normal, Side::Inside) // front_face = false
         } else {
             (*normal, Side::Outside) // front_face = true
@@ -58,12 +67,29 @@ impl HitRecord<'_> {
 }
 
 pub trait Hit: Sync + Send {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb>;
+
+    // The fraction of light getting through along the ray between t_min and t_max, for
+    // shadow rays. Surfaces block it all except through the holes of cutouts, and media
+    // can let some through.
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        let mut t_min = t_min;
+        while let Some(hr) = self.hit(ray, t_min, t_max) {
+            transmittance *= 1.0 - hr.material.opacity(ray, &hr);
+            if transmittance == 0.0 {
+                break;
+            }
+            // Past the surface, as the next ray of a path would start.
+            t_min = hr.t + 0.001;
+        }
+        transmittance
+    }
 }
 
 impl Hit for Objects {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut to_return: Option<HitRecord> = None;
         let mut closest_t = t_max;
 
@@ -91,4 +117,88 @@ impl Hit for Objects {
 
         output
     }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        for obj in self {
+            transmittance *= obj.transmittance(ray, t_min, t_max);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::*;
+    use crate::colour::*;
+    use crate::texture::*;
+
+    fn cutout_sphere(centre: Pos3, alpha: f64) -> Box<dyn Hit> {
+        let material = Material::Lambertian {
+            albedo: solid_colour(Colour::from(0.5)),
+        };
+        Box::new(Sphere::new(centre, 0.5, Material::cutout(material, constant(alpha))))
+    }
+
+    // A shadow ray goes through both sides of a sphere, so keeps (1 - alpha)^2 of the
+    // light, and gets past an opaque sphere only if it ends in front of it.
+    #[test]
+    fn shadows_go_through_cutouts() {
+        let ray = Ray::new(Pos3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+
+        for &alpha in &[0.0, 0.25, 1.0] {
+            let objects: Objects = vec![cutout_sphere(Pos3::new(2.0, 0.0, 0.0), alpha)];
+            let expected = (1.0 - alpha) * (1.0 - alpha);
+            assert!((objects.transmittance(&ray, 0.001, 10.0) - expected).abs() < 1e-12, "alpha {}", alpha);
+        }
+
+        let objects: Objects = vec![
+            cutout_sphere(Pos3::new(2.0, 0.0, 0.0), 0.0),
+            cutout_sphere(Pos3::new(4.0, 0.0, 0.0), 1.0),
+        ];
+        assert_eq!(objects.transmittance(&ray, 0.001, 3.0), 1.0);
+        assert_eq!(objects.transmittance(&ray, 0.001, 10.0), 0.0);
+
+        let bvh: Box<dyn Hit> = Box::new(Bvh::new(vec![cutout_sphere(Pos3::new(2.0, 0.0, 0.0), 0.5)], 0.0, 1.0));
+        assert!((bvh.transmittance(&ray, 0.001, 10.0) - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tangents_lie_in_the_surface() {
+        seed_rng(6);
+        let material = Material::Lambertian {
+            albedo: solid_colour(Colour::from(0.5)),
+        };
+        let objects: Vec<(Box<dyn Hit>, Pos3)> = vec![
+            (Box::new(Sphere::new(Pos3::new(1.0, -2.0, 0.5), 1.5, material.clone())), Pos3::new(1.0, -2.0, 0.5)),
+            (Box::new(XYRect::new(-1.0, 2.0, -3.0, 1.0, 0.5, material.clone())), Pos3::new(0.5, -1.0, 0.5)),
+            (Box::new(XZRect::new(-1.0, 2.0, -3.0, 1.0, 0.5, material.clone())), Pos3::new(0.5, 0.5, -1.0)),
+            (Box::new(YZRect::new(-1.0, 2.0, -3.0, 1.0, 0.5, material)), Pos3::new(0.5, 0.5, -1.0)),
+        ];
+
+        for (object, target) in &objects {
+            let mut hits = 0;
+            for _ in 0..100 {
+                // From all around, at points near the middle of the object.
+                let origin = *target + 10.0 * random_unit_vec();
+                let ray = Ray::new(origin, *target + 0.5 * random_vec_in_unit_sphere() - origin, 0.0);
+                let hr = match object.hit(&ray, 0.001, INF) {
+                    Some(hr) => hr,
+                    None => continue,
+                };
+                hits += 1;
+
+                for tangent in [hr.dpdu, hr.dpdv] {
+                    assert!(tangent.length() > 1e-6, "degenerate tangent {:?} at {:?}", tangent, hr.p);
+                    let cos = Vec3::dot(&Vec3::normalize(&tangent), &hr.normal);
+                    assert!(cos.abs() < 1e-9, "tangent {:?} against normal {:?} at {:?}", tangent, hr.normal, hr.p);
+                }
+            }
+            assert!(hits > 20);
+        }
+    }
 }
@@ -15,66 +15,78 @@ impl<O: Hit> ConstantMedium<O> {
         }
     }
 }
-impl<O: Hit> Hit for ConstantMedium<O> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        match self.boundary.hit(ray, std::f64::NEG_INFINITY, std::f64::INFINITY) {
-            None => None,
-            Some(hr1) => {
-                match self.boundary.hit(ray, hr1.t + 0.0001, std::f64::INFINITY) {
-                    None => None,
-                    Some(hr2) => {
-                        let mut min = if hr1.t < t_min {
-                            t_min
-                        } else {
-                            hr1.t
-                        };
-                        let max = if hr2.t > t_max {
-                            t_max
-                        } else {
-                            hr2.t
-                        };
 
-                        if min >= max {
-                            return None;
-                        }
+// The part of the ray between t_min and t_max inside a convex boundary, with where the
+// ray leaves it.
+pub fn inside<'a, O: Hit>(boundary: &'a O, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64, HitRecord<'a>)> {
+    let hr1 = boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+    let hr2 = boundary.hit(ray, hr1.t + 0.0001, f64::INFINITY)?;
+
+    let mut min = if hr1.t < t_min {
+        t_min
+    } else {
+        hr1.t
+    };
+    let max = if hr2.t > t_max {
+        t_max
+    } else {
+        hr2.t
+    };
 
-                        if min < 0.0 {
-                            min = 0.0;
-                        }
+    if min >= max {
+        return None;
+    }
 
-                        let ray_length = ray.direction.length();
-                        let distance_inside_boundary = (max - min) * ray_length;
-                        let hit_distance = self.neg_inv_density * rand::random::<f64>().ln();
+    if min < 0.0 {
+        min = 0.0;
+    }
 
-                        if hit_distance > distance_inside_boundary {
-                            return None;
-                        }
+    Some((min, max, hr2))
+}
 
-                        let t = min + hit_distance / ray_length;
-                        let p = ray.at(t);
+impl<O: Hit> Hit for ConstantMedium<O> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (min, max, hr2) = inside(&self.boundary, ray, t_min, t_max)?;
 
-                        let normal = Vec3::new(1.0, 0.0, 0.0);
-                        let side = Side::Outside;
-                        let material = &self.material;
+        let ray_length = ray.direction.length();
+        let distance_inside_boundary = (max - min) * ray_length;
+        let hit_distance = self.neg_inv_density * random_zero_one().ln();
 
-                        Some(
-                            HitRecord {
-                                t,
-                                p,
-                                normal,
-                                side,
-                                material,
-                                u: hr2.u,
-                                v: hr2.v,
-                            }
-                        )
-                    }
-                }
-            }
+        if hit_distance > distance_inside_boundary {
+            return None;
         }
+
+        let t = min + hit_distance / ray_length;
+        let p = ray.at(t);
+
+        let normal = Vec3::new(1.0, 0.0, 0.0);
+        let side = Side::Outside;
+        let material = &self.material;
+
+        Some(
+            HitRecord {
+                t,
+                p,
+                local: p,
+                normal,
+                side,
+                material,
+                u: hr2.u,
+                v: hr2.v,
+                dpdu: hr2.dpdu,
+                dpdv: hr2.dpdv,
+            }
+        )
     }
 
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
         self.boundary.bounding_box(t0, t1)
     }
-}
\ No newline at end of file
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match inside(&self.boundary, ray, t_min, t_max) {
+            Some((min, max, _)) => ((max - min) * ray.direction.length() / self.neg_inv_density).exp(),
+            None => 1.0,
+        }
+    }
+}
@@ -9,6 +9,7 @@ pub struct Cuboid {
 }
 
 impl Cuboid {
+    #[allow(clippy::vec_init_then_push)]
     pub fn new(c0: Pos3, c1: Pos3, material: Material) -> Cuboid {
         let mut sides: Vec<Box<dyn Hit>> = Vec::with_capacity(6);
 
@@ -102,7 +103,7 @@ impl Cuboid {
 }
 
 impl Hit for Cuboid {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         self.sides.hit(ray, t_min, t_max)
     }
 
@@ -0,0 +1,135 @@
+// A medium whose density varies through it, e.g. a cloud or smoke. Scattering is found
+// by delta tracking: tentative collisions are taken as if the medium had the density
+// max_density throughout, and each is real with probability the actual density over
+// that, the rest being null collisions that pass straight through. Shadow rays instead
+// use ratio tracking, taking the product over tentative collisions of the chance each
+// is null, which gives the transmittance with much less noise.
+
+use super::*;
+use crate::density::*;
+
+pub struct HeterogeneousMedium<O: Hit> {
+    boundary: O,
+    material: Material,
+    density: DensityField,
+    // The majorant, at least the density anywhere inside, higher making it slower.
+    max_density: f64,
+}
+
+impl<O: Hit> HeterogeneousMedium<O> {
+    pub fn new(boundary: O, density: DensityField, max_density: f64, material: Material) -> HeterogeneousMedium<O> {
+        HeterogeneousMedium {
+            boundary,
+            material,
+            density,
+            max_density,
+        }
+    }
+
+    // The chance a tentative collision at p is real.
+    fn real(&self, p: Pos3) -> f64 {
+        clamp(0.0, 1.0, (self.density)(p) / self.max_density)
+    }
+
+    // The next tentative collision after t.
+    fn step(&self, t: f64, ray_length: f64) -> f64 {
+        t - (1.0 - random_zero_one()).ln() / (self.max_density * ray_length)
+    }
+}
+
+impl<O: Hit> Hit for HeterogeneousMedium<O> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if self.max_density <= 0.0 {
+            return None;
+        }
+        let (min, max, hr2) = inside(&self.boundary, ray, t_min, t_max)?;
+
+        let ray_length = ray.direction.length();
+        let mut t = min;
+        loop {
+            t = self.step(t, ray_length);
+            if t >= max {
+                return None;
+            }
+
+            let p = ray.at(t);
+            if random_zero_one() < self.real(p) {
+                return Some(
+                    HitRecord {
+                        t,
+                        p,
+                        local: p,
+                        normal: Vec3::new(1.0, 0.0, 0.0),
+                        side: Side::Outside,
+                        material: &self.material,
+                        u: hr2.u,
+                        v: hr2.v,
+                        dpdu: hr2.dpdu,
+                        dpdv: hr2.dpdv,
+                    }
+                );
+            }
+        }
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+        self.boundary.bounding_box(t0, t1)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.max_density <= 0.0 {
+            return 1.0;
+        }
+        let (min, max) = match inside(&self.boundary, ray, t_min, t_max) {
+            Some((min, max, _)) => (min, max),
+            None => return 1.0,
+        };
+
+        let ray_length = ray.direction.length();
+        let mut transmittance = 1.0;
+        let mut t = min;
+        loop {
+            t = self.step(t, ray_length);
+            if t >= max {
+                return transmittance;
+            }
+
+            transmittance *= 1.0 - self.real(ray.at(t));
+            // Russian roulette once little gets through, keeping the expected value.
+            if transmittance < 0.1 {
+                if random_zero_one() * 0.1 >= transmittance {
+                    return 0.0;
+                }
+                transmittance = 0.1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::*;
+    use crate::texture::*;
+
+    #[test]
+    fn ratio_tracking_matches_beer_lambert() {
+        seed_rng(11);
+        let sigma = 0.5;
+        let material = Material::Isotropic {
+            albedo: solid_colour(Colour::from(0.5)),
+        };
+        let cube = Cuboid::new(Pos3::new(0.0, 0.0, 0.0), Pos3::new(3.0, 3.0, 3.0), material.clone());
+        // A majorant well above the density, so that most tentative collisions are null.
+        let medium = HeterogeneousMedium::new(cube, uniform(sigma), 2.5 * sigma, material);
+
+        // Not a unit direction, so t is half the distance.
+        let ray = Ray::new(Pos3::new(-1.0, 1.5, 1.5), Vec3::new(2.0, 0.0, 0.0), 0.0);
+        for &(t_max, distance) in &[(INF, 3.0), (1.25, 1.5)] {
+            let n = 20000;
+            let mean = (0..n).map(|_| medium.transmittance(&ray, 0.001, t_max)).sum::<f64>() / n as f64;
+            let expected = (-sigma * distance).exp();
+            assert!((mean - expected).abs() < 0.01, "transmittance {} over {}, expected {}", mean, distance, expected);
+        }
+    }
+}
@@ -25,7 +25,7 @@ impl XYRect {
 
 impl Hit for XYRect {
     #[allow(clippy::many_single_char_names)]
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.z - ray.origin.z) / (ray.direction.z);
         if t < t_min || t > t_max {
             return None;
@@ -48,8 +48,11 @@ impl Hit for XYRect {
                     t,
                     u,
                     v,
-                    p,  
+                    p,
+                    local: p,
                     side,
+                    dpdu: Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+                    dpdv: Vec3::new(0.0, self.y1 - self.y0, 0.0),
                 }
             )
         } else {
@@ -90,7 +93,7 @@ impl XZRect {
 }
 
 impl Hit for XZRect {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.y - ray.origin.y) / (ray.direction.y);
         if t < t_min || t > t_max {
             return None;
@@ -107,10 +110,13 @@ impl Hit for XZRect {
                     material: &self.material,
                     normal,
                     p: Pos3::new(x, self.y, z),
+                    local: Pos3::new(x, self.y, z),
                     t,
                     u: (x - self.x0) / (self.x1 - self.x0),
                     v: (z - self.z0) / (self.z1 - self.z0),
                     side,
+                    dpdu: Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+                    dpdv: Vec3::new(0.0, 0.0, self.z1 - self.z0),
                 }
             )
         } else {
@@ -151,7 +157,7 @@ impl YZRect {
 }
 
 impl Hit for YZRect {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.x - ray.origin.x) / (ray.direction.x);
         if t < t_min || t > t_max {
             return None;
@@ -168,10 +174,13 @@ impl Hit for YZRect {
                     material: &self.material,
                     normal,
                     p: Pos3::new(self.x, y, z),
+                    local: Pos3::new(self.x, y, z),
                     t,
                     v: (z - self.z0) / (self.z1 - self.z0),
                     u: (y - self.y0) / (self.y1 - self.y0),
                     side,
+                    dpdu: Vec3::new(0.0, self.y1 - self.y0, 0.0),
+                    dpdv: Vec3::new(0.0, 0.0, self.z1 - self.z0),
                 }
             )
         } else {
@@ -26,11 +26,21 @@ impl Sphere {
 
         (u, v)
     }
+
+    // The derivatives of a point on the sphere relative to the centre with respect to the
+    // uv coordinates above.
+    pub fn dpduv(p: Pos3) -> (Vec3, Vec3) {
+        let r_xz = (p.x * p.x + p.z * p.z).sqrt().max(1e-8);
+        let dpdu = 2.0 * PI * Vec3::new(p.z, 0.0, -p.x);
+        let dpdv = PI * Vec3::new(-p.y * p.x / r_xz, r_xz, -p.y * p.z / r_xz);
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hit for Sphere {
     #[allow(clippy::many_single_char_names)]
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = ray.origin - self.centre;
         let a = ray.direction.length_squared();
         let half_b = Vec3::dot(&oc, &ray.direction);
@@ -50,6 +60,7 @@ impl Hit for Sphere {
                 let material = &self.material;
 
                 let (u, v) = Sphere::uv(Vec3::normalize(&(p - self.centre)));
+                let (dpdu, dpdv) = Sphere::dpduv(p - self.centre);
 
                 return Some(
                     HitRecord {
@@ -57,9 +68,12 @@ impl Hit for Sphere {
                         u,
                         v,
                         p,
+                        local: p,
                         normal,
                         side,
                         material,
+                        dpdu,
+                        dpdv,
                     }
                 );
             } 
@@ -70,11 +84,12 @@ impl Hit for Sphere {
                 let normal = Vec3::normalize(&(p - self.centre));
                 
 
-                let (_, side) = HitRecord::face(&normal, &ray);
+                let (_, side) = HitRecord::face(&normal, ray);
 
                 let material = &self.material;
 
                 let (u, v) = Sphere::uv(Vec3::normalize(&(p - self.centre)));
+                let (dpdu, dpdv) = Sphere::dpduv(p - self.centre);
 
                 return Some(
                     HitRecord {
@@ -82,9 +97,12 @@ impl Hit for Sphere {
                         u,
                         v,
                         p,
+                        local: p,
                         normal,
                         side,
                         material,
+                        dpdu,
+                        dpdv,
                     }
                 );
             } 
@@ -17,7 +17,7 @@ impl<O: Hit> LinearMove<O> {
 }
 
 impl<O: Hit> Hit for LinearMove<O> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         self.obj.hit(
             &Ray {
                 origin: ray.origin - self.vel * ray.time,
@@ -28,6 +28,17 @@ impl<O: Hit> Hit for LinearMove<O> {
         )
     }
 
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.obj.transmittance(
+            &Ray {
+                origin: ray.origin - self.vel * ray.time,
+                ..*ray
+            },
+            t_min,
+            t_max,
+        )
+    }
+
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
         if let Some(bound) = self.obj.bounding_box(t0, t1) {
             Aabb::surround(
@@ -61,23 +72,22 @@ impl<O: Hit> FlipNormals<O> {
 }
 
 impl<O: Hit> Hit for FlipNormals<O> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        if let Some(hr) = self.0.hit(ray, t_min, t_max) {
-            Some(
-                HitRecord {
-                    side: !hr.side,
-                    ..hr
-                }
-            )
-        }
-        else {
-            None
-        }
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.0.hit(ray, t_min, t_max).map(|hr|
+            HitRecord {
+                side: !hr.side,
+                ..hr
+            }
+        )
     }
 
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
         self.0.bounding_box(t0, t1)
     }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.0.transmittance(ray, t_min, t_max)
+    }
 }
 
 pub struct Translate<O: Hit> {
@@ -95,7 +105,7 @@ impl <O: Hit> Translate<O> {
 }
 
 impl<O: Hit> Hit for Translate<O> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let new_ray = Ray {
             origin: ray.origin - self.offset,
             ..*ray
@@ -125,6 +135,15 @@ impl<O: Hit> Hit for Translate<O> {
             }
         )
     }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let new_ray = Ray {
+            origin: ray.origin - self.offset,
+            ..*ray
+        };
+
+        self.obj.transmittance(&new_ray, t_min, t_max)
+    }
 } 
 
 pub struct RotateY<O: Hit> {
@@ -148,7 +167,7 @@ impl<O: Hit> RotateY<O> {
 }
 
 impl<O: Hit> Hit for RotateY<O> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         fn rot(p: Pos3, sin_theta: f64, cos_theta: f64) -> Pos3 {   
             Vec3::new(
                 Vec3::dot(&p, &Vec3::new(cos_theta, 0.0, sin_theta)),
@@ -167,6 +186,8 @@ impl<O: Hit> Hit for RotateY<O> {
             HitRecord {
                 p: rot(hr.p, self.sin_theta, self.cos_theta),
                 normal: rot(hr.normal, self.sin_theta, self.cos_theta),
+                dpdu: rot(hr.dpdu, self.sin_theta, self.cos_theta),
+                dpdv: rot(hr.dpdv, self.sin_theta, self.cos_theta),
                 ..hr
             }
         )
@@ -182,17 +203,17 @@ impl<O: Hit> Hit for RotateY<O> {
         }
 
         self.obj.bounding_box(t0, t1).map(|bb| {
-            let mut min = Vec3::from(std::f64::INFINITY);
-            let mut max = Vec3::from(std::f64::NEG_INFINITY);
+            let mut min = Vec3::from(f64::INFINITY);
+            let mut max = Vec3::from(f64::NEG_INFINITY);
 
-            for x in 0..3 {
-                for y in 0..3 {
-                    for z in 0..3 {
+            for x in 0..2 {
+                for y in 0..2 {
+                    for z in 0..2 {
                         let xyz = Vec3::new(x as f64, y as f64, z as f64);
 
-                        let xyz = xyz * bb.min + xyz.map(|c| 1.0 - c) * bb.min;
+                        let xyz = xyz * bb.max + xyz.map(|c| 1.0 - c) * bb.min;
 
-                        let tester = rot(xyz, self.cos_theta, self.sin_theta);
+                        let tester = rot(xyz, self.sin_theta, self.cos_theta);
 
                         for c in 0..3 {
                             min[c] = min[c].min(tester[c]);
@@ -208,4 +229,65 @@ impl<O: Hit> Hit for RotateY<O> {
             }
         })
     }
-}
\ No newline at end of file
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        fn rot(p: Pos3, sin_theta: f64, cos_theta: f64) -> Pos3 {
+            Vec3::new(
+                Vec3::dot(&p, &Vec3::new(cos_theta, 0.0, sin_theta)),
+                Vec3::dot(&p, &Vec3::new(0.0, 1.0, 0.0)),
+                Vec3::dot(&p, &Vec3::new(-sin_theta, 0.0, cos_theta)),
+            )
+        }
+
+        let new_ray = Ray {
+            origin: rot(ray.origin, -self.sin_theta, self.cos_theta),
+            direction: rot(ray.direction, -self.sin_theta, self.cos_theta),
+            ..*ray
+        };
+
+        self.obj.transmittance(&new_ray, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::*;
+    use crate::texture::*;
+
+    #[test]
+    fn rotate_y_bounding_box_contains_rotated_object() {
+        let (c0, c1) = (Pos3::new(1.0, -0.5, 2.0), Pos3::new(3.0, 1.0, 5.0));
+        let material = Material::Lambertian {
+            albedo: solid_colour(Colour::from(0.5)),
+        };
+
+        for &deg in &[0.0, 30.0, 90.0, 135.0, 250.0] {
+            let rotated = RotateY::new(Cuboid::new(c0, c1, material.clone()), deg);
+            let bb = rotated.bounding_box(0.0, 1.0).unwrap();
+            let contains = |p: Pos3| (0..3).all(|c| p[c] >= bb.min[c] - 1e-9 && p[c] <= bb.max[c] + 1e-9);
+
+            // The corners turned the way hit turns points back out of the object.
+            let (sin, cos) = (deg_to_rad(deg).sin(), deg_to_rad(deg).cos());
+            for &x in &[c0.x, c1.x] {
+                for &y in &[c0.y, c1.y] {
+                    for &z in &[c0.z, c1.z] {
+                        let corner = Pos3::new(cos * x + sin * z, y, -sin * x + cos * z);
+                        assert!(contains(corner), "corner {:?} outside {:?} at {} degrees", corner, bb, deg);
+                    }
+                }
+            }
+
+            // And everywhere rays actually hit it.
+            let centre = 0.5 * (bb.min + bb.max);
+            for i in 0..200 {
+                let phi = 2.0 * PI * i as f64 / 200.0;
+                let origin = centre + 20.0 * Vec3::new(phi.cos(), 0.3 * (3.0 * phi).sin(), phi.sin());
+                let target = centre + Vec3::new((7.0 * phi).sin(), 0.5 * (5.0 * phi).cos(), (3.0 * phi).cos());
+                if let Some(hr) = rotated.hit(&Ray::new(origin, target - origin, 0.0), 0.001, INF) {
+                    assert!(contains(hr.p), "hit {:?} outside {:?} at {} degrees", hr.p, bb, deg);
+                }
+            }
+        }
+    }
+}
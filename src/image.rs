@@ -1,8 +1,6 @@
 use crate::colour::*;
-use crate::consts::*;
+use crate::film::*;
 
-use rayon::prelude::*;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::path::Path;
 use image::*;
 
@@ -11,25 +9,28 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn new(width: usize, height: usize, f: impl Fn(usize, usize) -> Colour + Sync) -> Image {
-        let num_lines: AtomicUsize = AtomicUsize::new(1);
+    pub fn from_film(film: &Film) -> Image {
+        let rect = film.rect;
         Image {
-            pixels: (0..height)
-                        .into_par_iter()
-                        .rev()
-                        .map(|y| {
-                                eprintln!("Starting scanline {} ({}/{})", y, num_lines.fetch_add(1, Ordering::Relaxed), IMAGE_HEIGHT);
-                                (0..width)
-                                .map(|x|
-                                    f(x, y)
-                                ).collect()
-                            }
+            pixels: (rect.y..rect.y + rect.height)
+                        .map(|y|
+                            (rect.x..rect.x + rect.width)
+                                .map(|x| film.pixel(x, y))
+                                .collect()
                         ).collect(),
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.pixels.first().map_or(0, Vec::len)
+    }
+
+    pub fn height(&self) -> usize {
+        self.pixels.len()
+    }
+
     pub fn print(self) {
-        print!("P3\n{} {}\n255\n", IMAGE_WIDTH, IMAGE_HEIGHT);
+        print!("P3\n{} {}\n255\n", self.width(), self.height());
         for row in self.pixels {
             for col in row {
                 debug_assert!(col.all_positive_or_zero());
@@ -39,9 +40,19 @@ impl Image {
     }
 
     pub fn save(self) {
-        let image_path = Path::new("out/image.png");
+        self.save_to(Path::new("out/image.png"));
+    }
 
-        let mut img = RgbImage::new(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32);
+    pub fn save_to(self, image_path: &Path) {
+        if let Some(dir) = image_path.parent() {
+            std::fs::create_dir_all(dir).expect("Failed creating output directory.");
+        }
+
+        self.to_rgb_image().save(image_path).expect("Failed writing PNG.");
+    }
+
+    pub fn to_rgb_image(&self) -> RgbImage {
+        let mut img = RgbImage::new(self.width() as u32, self.height() as u32);
 
         for (y, row) in self.pixels.iter().enumerate() {
             for (x, col) in row.iter().enumerate() {
@@ -50,6 +61,6 @@ impl Image {
             }
         }
 
-        img.save(image_path).expect("Failed writing PNG.");
+        img
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,238 @@
+// Lights that are sampled directly, as part of the direct lighting of the surfaces they
+// shine on. Delta lights aren't objects in the scene, so can't be hit by rays and are
+// only seen that way. Emitters are objects that are also hit by rays, but whose emission
+// isn't counted by paths that could have sampled them instead.
+
+pub mod ies;
+pub mod alias_table;
+pub mod light_bvh;
+pub mod sampler;
+
+pub use ies::*;
+pub use alias_table::*;
+pub use light_bvh::*;
+pub use sampler::*;
+
+use crate::aabb::*;
+use crate::colour::*;
+use crate::hit::*;
+use crate::material::*;
+use crate::onb::*;
+use crate::ray::*;
+use crate::utility::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// Point and spot lights can have the measured distribution of a fixture, in which case
+// intensity is that in its brightest direction. The fixture of a point light hangs
+// straight down and that of a spot light points along its direction, both with their
+// horizontal angle 0 along x.
+#[derive(Clone)]
+pub enum Light {
+    // Shining equally in all directions, intensity being the radiant intensity.
+    Point {
+        position: Pos3,
+        intensity: Colour,
+        profile: Option<Arc<IesProfile>>,
+    },
+    // A point light whose emission falls off away from its direction.
+    Spot {
+        position: Pos3,
+        direction: Vec3,
+        intensity: Colour,
+        spot: Spot,
+        profile: Option<Arc<IesProfile>>,
+    },
+    // Infinitely far away, e.g. the sun, direction being that the light travels in.
+    Directional {
+        direction: Vec3,
+        irradiance: Colour,
+    },
+    // A sphere with an emissive material, e.g. a bulb.
+    Sphere(Sphere),
+}
+
+// Light arriving at a point from a light.
+pub struct LightSample {
+    // The unit direction to the light.
+    pub wi: Vec3,
+    pub distance: f64,
+    // The irradiance from a delta light on a surface facing it. For an emitter one over
+    // the probability density of wi, to be multiplied by what it emits back along wi.
+    pub weight: Colour,
+}
+
+impl Light {
+    // Light arriving at p, None if there is none.
+    pub fn sample(&self, p: Pos3) -> Option<LightSample> {
+        let (wi, distance, weight) = match self {
+            Light::Point { position, intensity, profile } => {
+                let (wi, distance) = towards(p, *position);
+                let falloff = profile_falloff(profile, Vec3::new(0.0, -1.0, 0.0), -wi);
+                (wi, distance, falloff * *intensity / (distance * distance))
+            },
+            Light::Spot { position, direction, intensity, spot, profile } => {
+                let (wi, distance) = towards(p, *position);
+                let falloff = spot.falloff(Vec3::dot(&-wi, &Vec3::normalize(direction)))
+                    * profile_falloff(profile, *direction, -wi);
+                (wi, distance, falloff * *intensity / (distance * distance))
+            },
+            Light::Directional { direction, irradiance } => {
+                (-Vec3::normalize(direction), INF, *irradiance)
+            },
+            Light::Sphere(sphere) => {
+                // Uniformly within the cone of directions that see the sphere.
+                let (to_centre, d) = towards(p, sphere.centre);
+                let sin2_max = sphere.radius * sphere.radius / (d * d);
+                if sin2_max >= 1.0 {
+                    return None;
+                }
+                let cos_max = (1.0 - sin2_max).sqrt();
+                let one_minus_cos_max = sin2_max / (1.0 + cos_max);
+
+                let cos = 1.0 - random_zero_one() * one_minus_cos_max;
+                let sin = (1.0 - cos * cos).max(0.0).sqrt();
+                let phi = 2.0 * PI * random_zero_one();
+                let wi = Onb::from_w(to_centre).to_world(Vec3::new(sin * phi.cos(), sin * phi.sin(), cos));
+
+                let distance = d * cos - (sphere.radius * sphere.radius - d * d * sin * sin).max(0.0).sqrt();
+                (wi, distance, Colour::from(2.0 * PI * one_minus_cos_max))
+            },
+        };
+
+        if weight.all(|c| c == 0.0) {
+            return None;
+        }
+
+        Some(LightSample { wi, distance, weight })
+    }
+
+    // The probability density of sample picking the unit direction wi from p, 0 for delta
+    // lights.
+    pub fn pdf(&self, p: Pos3, wi: Vec3) -> f64 {
+        match self {
+            Light::Sphere(sphere) => {
+                let (to_centre, d) = towards(p, sphere.centre);
+                let sin2_max = sphere.radius * sphere.radius / (d * d);
+                if sin2_max >= 1.0 {
+                    return 0.0;
+                }
+                let cos_max = (1.0 - sin2_max).sqrt();
+                if Vec3::dot(&wi, &to_centre) < cos_max {
+                    return 0.0;
+                }
+
+                1.0 / (2.0 * PI * sin2_max / (1.0 + cos_max))
+            },
+            _ => 0.0,
+        }
+    }
+
+    pub fn is_emitter(&self) -> bool {
+        matches!(self, Light::Sphere(_))
+    }
+
+    // What an emitter emits back along a ray towards it, white for delta lights.
+    pub fn emitted(&self, ray: &Ray) -> Colour {
+        match self {
+            Light::Sphere(sphere) => match sphere.hit(ray, 0.001, INF) {
+                Some(hr) => hr.material.emit(ray, &hr),
+                None => Colour::BLACK,
+            },
+            _ => Colour::WHITE,
+        }
+    }
+
+    // A rough estimate of the total power emitted, for choosing between lights.
+    pub fn power(&self) -> f64 {
+        match self {
+            Light::Point { intensity, .. } => 4.0 * PI * intensity.luminance(),
+            Light::Spot { intensity, spot, .. } => {
+                2.0 * PI * (1.0 - deg_to_rad(spot.outer_angle.min(180.0)).cos()) * intensity.luminance()
+            },
+            // Depends on the size of the scene, these are always sampled.
+            Light::Directional { .. } => INF,
+            Light::Sphere(sphere) => {
+                // From the radiance seen from straight above.
+                let top = sphere.centre + Vec3::new(0.0, 2.0 * sphere.radius, 0.0);
+                let radiance = self.emitted(&Ray::new(top, Vec3::new(0.0, -1.0, 0.0), 0.0));
+                PI * 4.0 * PI * sphere.radius * sphere.radius * radiance.luminance()
+            },
+        }
+    }
+
+    // Where the light is and which way it shines, None for lights at infinity.
+    pub fn bounds(&self) -> Option<(Aabb, LightCone)> {
+        match self {
+            Light::Point { position, .. } => Some((Aabb::new(*position, *position), LightCone::ALL)),
+            Light::Spot { position, direction, spot, .. } => Some((
+                Aabb::new(*position, *position),
+                LightCone {
+                    axis: Vec3::normalize(direction),
+                    theta_o: deg_to_rad(spot.outer_angle.min(180.0)),
+                    theta_e: 0.0,
+                },
+            )),
+            Light::Directional { .. } => None,
+            Light::Sphere(sphere) => {
+                let r = Vec3::new(sphere.radius, sphere.radius, sphere.radius);
+                Some((Aabb::new(sphere.centre - r, sphere.centre + r), LightCone::ALL))
+            },
+        }
+    }
+}
+
+// The relative intensity in direction out of a fixture pointing along nadir.
+fn profile_falloff(profile: &Option<Arc<IesProfile>>, nadir: Vec3, out: Vec3) -> f64 {
+    match profile {
+        Some(profile) => profile.intensity(IesProfile::frame(nadir, Vec3::new(1.0, 0.0, 0.0)).to_local(out)),
+        None => 1.0,
+    }
+}
+
+fn towards(p: Pos3, position: Pos3) -> (Vec3, f64) {
+    let to_light = position - p;
+    let distance = to_light.length();
+    (to_light / distance, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_and_spot_lights_fall_off_with_the_square_of_distance() {
+        let position = Pos3::new(1.0, 4.0, -2.0);
+        let intensity = Colour::new(3.0, 2.0, 1.0);
+        let lights = [
+            Light::Point { position, intensity, profile: None },
+            Light::Spot {
+                position,
+                direction: Vec3::new(0.0, -2.0, 0.0),
+                intensity,
+                spot: Spot { inner_angle: 30.0, outer_angle: 45.0 },
+                profile: None,
+            },
+        ];
+
+        for light in &lights {
+            for &d in &[0.5, 1.0, 2.0, 7.0] {
+                // Straight below, along the axis of the spot.
+                let p = position - Vec3::new(0.0, d, 0.0);
+                let sample = light.sample(p).unwrap();
+                assert!((sample.distance - d).abs() < 1e-12);
+                assert!((sample.wi.y - 1.0).abs() < 1e-12);
+
+                let expected = intensity / (d * d);
+                assert!(
+                    sample.weight.zip_with(expected, |a, b| (a - b).abs() / b).all(|e| e < 1e-12),
+                    "{:?} vs {:?} at {}", sample.weight, expected, d,
+                );
+            }
+        }
+
+        // Nothing from the spot out to the side.
+        assert!(lights[1].sample(position + Vec3::new(3.0, 0.0, 0.0)).is_none());
+    }
+}
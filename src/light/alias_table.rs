@@ -0,0 +1,86 @@
+// Sampling from a discrete distribution in constant time, after "A Linear Algorithm for
+// Generating Random Numbers with a Given Distribution", Vose 1991. Each bin holds the
+// probability of keeping its own index and the index it otherwise hands over to.
+
+use crate::utility::*;
+
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    bins: Vec<(f64, usize)>,
+    pmf: Vec<f64>,
+}
+
+impl AliasTable {
+    // None if no weight is positive. Negative and non-finite weights count as 0.
+    pub fn new(weights: &[f64]) -> Option<AliasTable> {
+        let weights: Vec<f64> = weights.iter().map(|&w| if w.is_finite() && w > 0.0 { w } else { 0.0 }).collect();
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let n = weights.len();
+        let pmf: Vec<f64> = weights.iter().map(|w| w / total).collect();
+        let mut scaled: Vec<f64> = pmf.iter().map(|p| p * n as f64).collect();
+        // Bins left over at the end are full up to rounding.
+        let mut bins: Vec<(f64, usize)> = (0..n).map(|i| (1.0, i)).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| scaled[i] < 1.0);
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            bins[s] = (scaled[s], l);
+            scaled[l] -= 1.0 - scaled[s];
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+
+        Some(AliasTable { bins, pmf })
+    }
+
+    // The probability of sampling index i.
+    pub fn pmf(&self, i: usize) -> f64 {
+        self.pmf[i]
+    }
+
+    // An index with its probability.
+    pub fn sample(&self) -> (usize, f64) {
+        let u = random_zero_one() * self.bins.len() as f64;
+        let i = (u as usize).min(self.bins.len() - 1);
+        let (keep, alias) = self.bins[i];
+        let index = if (u - i as f64) < keep { i } else { alias };
+
+        (index, self.pmf[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pmf_sums_to_one_and_matches_the_bins() {
+        let weights = [3.0, 0.0, 1.0, -2.0, 0.5, f64::NAN, 7.5];
+        let table = AliasTable::new(&weights).unwrap();
+        let n = weights.len();
+
+        let sum: f64 = (0..n).map(|i| table.pmf(i)).sum();
+        assert!((sum - 1.0).abs() < 1e-12);
+        for &i in &[1, 3, 5] {
+            assert_eq!(table.pmf(i), 0.0);
+        }
+
+        // The probability of each index implied by the bins, each bin being picked with
+        // probability 1 / n.
+        let mut implied = vec![0.0; n];
+        for (i, &(keep, alias)) in table.bins.iter().enumerate() {
+            implied[i] += keep / n as f64;
+            implied[alias] += (1.0 - keep) / n as f64;
+        }
+        for (i, implied) in implied.iter().enumerate() {
+            assert!((implied - table.pmf(i)).abs() < 1e-12, "index {}: {} against {}", i, implied, table.pmf(i));
+        }
+
+        assert!(AliasTable::new(&[0.0, -1.0]).is_none());
+    }
+}
@@ -0,0 +1,292 @@
+// Measured light distributions of real fixtures, from IES LM-63 photometric files.
+//
+// Only type C photometry is supported, which is what almost all fixtures use. Its
+// vertical angles go from 0 at the nadir, straight down out of the fixture, to 180
+// straight up, and its horizontal angles go round the vertical, 0 being along the
+// length of the fixture and 90 a quarter turn anticlockwise seen from above.
+
+use crate::onb::*;
+use crate::utility::*;
+use crate::vec3::*;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    vertical_angles: Vec<f64>,
+    horizontal_angles: Vec<f64>,
+    // One row of vertical angles per horizontal angle, scaled so that the brightest
+    // direction has intensity 1.
+    intensities: Vec<f64>,
+}
+
+const PHOTOMETRIC_TYPE_C: usize = 1;
+// Far more angles or tilt pairs than any real file has, so that a corrupt count fails to
+// parse rather than running out of memory.
+const MAX_COUNT: usize = 100_000;
+
+impl IesProfile {
+    pub fn load(path: &Path) -> io::Result<IesProfile> {
+        let text = fs::read_to_string(path)?;
+        IesProfile::parse(&text).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<IesProfile, String> {
+        // Keywords like [MANUFAC] come first and are only informative, the data starts
+        // after the TILT line.
+        let mut lines = text.lines();
+        let tilt = loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with("TILT=") => break line.trim_start()["TILT=".len()..].trim(),
+                Some(_) => continue,
+                None => return Err("missing TILT line".to_string()),
+            }
+        };
+
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .map(|word| word.parse::<f64>().map_err(|_| format!("invalid number {:?}", word)));
+        let mut next = || numbers.next().unwrap_or_else(|| Err("unexpected end of file".to_string()));
+
+        match tilt {
+            "NONE" => {},
+            // Lamp tilt factors, which don't matter for a fixture that isn't tilted.
+            "INCLUDE" => {
+                let _geometry = next()?;
+                let pairs = count(next()?)?;
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            },
+            _ => return Err(format!("unsupported TILT={}", tilt)),
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let _multiplier = next()?;
+        let vertical_count = count(next()?)?;
+        let horizontal_count = count(next()?)?;
+        let photometric_type = count(next()?)?;
+        if photometric_type != PHOTOMETRIC_TYPE_C {
+            return Err(format!("unsupported photometric type {}", photometric_type));
+        }
+        // Units, dimensions of the luminous opening, ballast factor, future use and watts.
+        for _ in 0..7 {
+            next()?;
+        }
+
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err("no angles".to_string());
+        }
+        let vertical_angles = (0..vertical_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..horizontal_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+        let mut intensities = (0..vertical_count * horizontal_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+
+        let sorted = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+        if !sorted(&vertical_angles) || !sorted(&horizontal_angles) {
+            return Err("angles not increasing".to_string());
+        }
+
+        let max = intensities.iter().cloned().fold(0.0, f64::max);
+        if max <= 0.0 {
+            return Err("no light emitted".to_string());
+        }
+        for intensity in &mut intensities {
+            *intensity = intensity.max(0.0) / max;
+        }
+
+        Ok(IesProfile {
+            vertical_angles,
+            horizontal_angles,
+            intensities,
+        })
+    }
+
+    // The intensity in a direction in the frame of the fixture, the nadir being along z
+    // and the horizontal angle 0 along x, relative to that of the brightest direction.
+    pub fn intensity(&self, local: Vec3) -> f64 {
+        let local = Vec3::normalize(&local);
+        let vertical = clamp(-1.0, 1.0, local.z).acos().to_degrees();
+        let horizontal = self.fold_horizontal(local.y.atan2(local.x).to_degrees().rem_euclid(360.0));
+
+        // Outside the measured range of vertical angles no light is emitted.
+        let (v0, v1, tv) = match interval(&self.vertical_angles, vertical) {
+            Some(interval) => interval,
+            None => return 0.0,
+        };
+        let (h0, h1, th) = interval(&self.horizontal_angles, horizontal).unwrap_or_else(|| {
+            // Past the last horizontal angle of a table that doesn't go all the way round.
+            let last = self.horizontal_angles.len() - 1;
+            (last, last, 0.0)
+        });
+
+        let at = |h: usize, v: usize| self.intensities[h * self.vertical_angles.len() + v];
+        let row = |h: usize| (1.0 - tv) * at(h, v0) + tv * at(h, v1);
+        (1.0 - th) * row(h0) + th * row(h1)
+    }
+
+    // The horizontal angle within those measured, using the symmetry implied by the last
+    // one.
+    fn fold_horizontal(&self, angle: f64) -> f64 {
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if last == 0.0 {
+            // Rotationally symmetric.
+            0.0
+        } else if last == 90.0 {
+            // Symmetric in each quadrant.
+            let angle = angle % 180.0;
+            if angle > 90.0 { 180.0 - angle } else { angle }
+        } else if last == 180.0 {
+            // Symmetric about the 0-180 plane.
+            if angle > 180.0 { 360.0 - angle } else { angle }
+        } else {
+            angle
+        }
+    }
+
+    // The frame of a fixture pointing its nadir along direction, with its horizontal
+    // angle 0 along the part of reference perpendicular to that, or any way round if
+    // there is no such part.
+    pub fn frame(direction: Vec3, reference: Vec3) -> Onb {
+        let w = Vec3::normalize(&direction);
+        let u = reference - Vec3::dot(&reference, &w) * w;
+        if u.length_squared() < 1e-16 {
+            return Onb::from_w(w);
+        }
+        let u = Vec3::normalize(&u);
+
+        // Anticlockwise seen from above, i.e. looking along the nadir.
+        Onb {
+            u,
+            v: Vec3::cross(&u, &w),
+            w,
+        }
+    }
+}
+
+// A number of things in the file, e.g. angles.
+fn count(value: f64) -> Result<usize, String> {
+    if value.fract() != 0.0 || !(0.0..=MAX_COUNT as f64).contains(&value) {
+        return Err(format!("invalid count {}", value));
+    }
+    Ok(value as usize)
+}
+
+// The indices of the angles either side of angle and how far it is between them, None if
+// it is outside all of them.
+fn interval(angles: &[f64], angle: f64) -> Option<(usize, usize, f64)> {
+    let last = angles.len() - 1;
+    if angle < angles[0] || angle > angles[last] {
+        return None;
+    }
+    if last == 0 {
+        return Some((0, 0, 0.0));
+    }
+
+    let i = angles.windows(2).position(|w| angle <= w[1]).unwrap_or(last - 1);
+    let t = (angle - angles[i]) / (angles[i + 1] - angles[i]);
+    Some((i, i + 1, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(values: &[f64]) -> String {
+        values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+    }
+
+    fn ies(tilt: &str, vertical: &[f64], horizontal: &[f64], intensities: &[f64]) -> String {
+        format!(
+            "IESNA:LM-63-2002\n[TEST] unit test\nTILT={}\n1 1000 1.0 {} {} 1 2 0.1 0.1 0.0\n1.0 1.0 12\n{}\n{}\n{}\n",
+            tilt, vertical.len(), horizontal.len(), join(vertical), join(horizontal), join(intensities),
+        )
+    }
+
+    // In the frame of the fixture, at vertical angle v from the nadir and horizontal angle h.
+    fn direction(v: f64, h: f64) -> Vec3 {
+        let (v, h) = (v.to_radians(), h.to_radians());
+        Vec3::new(v.sin() * h.cos(), v.sin() * h.sin(), v.cos())
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn interpolates_vertically_and_is_dark_outside() {
+        let profile = IesProfile::parse(&ies("NONE", &[0.0, 45.0, 90.0], &[0.0], &[200.0, 100.0, 0.0])).unwrap();
+
+        assert!(close(profile.intensity(direction(0.0, 0.0)), 1.0));
+        assert!(close(profile.intensity(direction(22.5, 30.0)), 0.75));
+        assert!(close(profile.intensity(direction(45.0, 200.0)), 0.5));
+        assert!(close(profile.intensity(direction(67.5, 0.0)), 0.25));
+        assert_eq!(profile.intensity(direction(120.0, 0.0)), 0.0);
+        // Only the direction matters, not its length.
+        assert!(close(profile.intensity(3.0 * direction(22.5, 0.0)), 0.75));
+    }
+
+    #[test]
+    fn tilt_is_skipped() {
+        let (vertical, horizontal, intensities) = ([0.0, 90.0], [0.0], [100.0, 50.0]);
+        let none = ies("NONE", &vertical, &horizontal, &intensities);
+        let include = ies("INCLUDE\n1\n3\n0 45 90\n1.0 0.9 0.7", &vertical, &horizontal, &intensities);
+        let (none, include) = (IesProfile::parse(&none).unwrap(), IesProfile::parse(&include).unwrap());
+
+        for v in 0..=9 {
+            let d = direction(10.0 * v as f64, 0.0);
+            assert_eq!(include.intensity(d), none.intensity(d));
+        }
+        assert!(IesProfile::parse(&ies("FILE.TLT", &vertical, &horizontal, &intensities)).is_err());
+    }
+
+    // The last horizontal angle tells the symmetry: 0 for none around the vertical, 90
+    // for the same in each quadrant and 180 for mirrored about the 0-180 plane.
+    #[test]
+    fn horizontal_symmetry() {
+        let vertical = [0.0, 90.0];
+
+        let round = IesProfile::parse(&ies("NONE", &vertical, &[0.0], &[100.0, 100.0])).unwrap();
+        for h in 0..8 {
+            assert!(close(round.intensity(direction(45.0, 45.0 * h as f64)), 1.0));
+        }
+
+        let quadrants = IesProfile::parse(&ies("NONE", &vertical, &[0.0, 90.0], &[100.0, 100.0, 50.0, 50.0])).unwrap();
+        for &(h, expected) in &[(0.0, 1.0), (45.0, 0.75), (90.0, 0.5), (135.0, 0.75), (180.0, 1.0), (270.0, 0.5), (315.0, 0.75)] {
+            assert!(close(quadrants.intensity(direction(45.0, h)), expected), "{} degrees", h);
+        }
+
+        let halves = IesProfile::parse(&ies("NONE", &vertical, &[0.0, 90.0, 180.0], &[100.0, 100.0, 50.0, 50.0, 20.0, 20.0])).unwrap();
+        for &(h, expected) in &[(0.0, 1.0), (90.0, 0.5), (135.0, 0.35), (180.0, 0.2), (225.0, 0.35), (270.0, 0.5), (315.0, 0.75)] {
+            assert!(close(halves.intensity(direction(45.0, h)), expected), "{} degrees", h);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let good = ies("NONE", &[0.0, 90.0], &[0.0], &[100.0, 50.0]);
+        assert!(IesProfile::parse(&good).is_ok());
+
+        // Missing data.
+        assert!(IesProfile::parse(good.trim_end().trim_end_matches("50")).is_err());
+        assert!(IesProfile::parse(&good.replace("TILT=NONE", "")).is_err());
+        assert!(IesProfile::parse(&ies("NONE", &[0.0, 90.0], &[0.0], &[0.0, 0.0])).is_err());
+        assert!(IesProfile::parse(&ies("NONE", &[90.0, 0.0], &[0.0], &[100.0, 50.0])).is_err());
+
+        // Counts that aren't counts.
+        for count in &["2.5", "-2", "1e300", "NaN", "inf"] {
+            let text = good.replace("1.0 2 1 1 2", &format!("1.0 {} 1 1 2", count));
+            assert_ne!(text, good);
+            assert!(IesProfile::parse(&text).is_err(), "count {}", count);
+        }
+        let text = ies("INCLUDE\n1\n1e20\n0\n1", &[0.0, 90.0], &[0.0], &[100.0, 50.0]);
+        assert!(IesProfile::parse(&text).is_err());
+    }
+}
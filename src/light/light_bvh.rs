@@ -0,0 +1,275 @@
+// Choosing a light by how much it is likely to contribute at a point, after "Importance
+// Sampling of Many Lights with Adaptive Tree Splitting", Conty Estevez and Kulla 2018.
+// Nodes bound the position, emitted power and directions of emission of their lights,
+// and a light is chosen by walking down from the root, picking each child in proportion
+// to a conservative estimate of the light it gives at the point.
+
+use super::*;
+
+// The directions light leaves in: the normals of the emitting surfaces lie within
+// theta_o of the axis, and light leaves within theta_e of the normals. Angles are in
+// radians.
+#[derive(Debug, Clone, Copy)]
+pub struct LightCone {
+    pub axis: Vec3,
+    pub theta_o: f64,
+    pub theta_e: f64,
+}
+
+impl LightCone {
+    // Emitting in all directions.
+    pub const ALL: LightCone = LightCone {
+        axis: Vec3 { x: 0.0, y: 0.0, z: 1.0 },
+        theta_o: PI,
+        theta_e: PI / 2.0,
+    };
+
+    // The smallest cone around both.
+    pub fn union(a: LightCone, b: LightCone) -> LightCone {
+        let (a, b) = if b.theta_o > a.theta_o { (b, a) } else { (a, b) };
+        let theta_e = a.theta_e.max(b.theta_e);
+        let theta_d = clamp(-1.0, 1.0, Vec3::dot(&a.axis, &b.axis)).acos();
+
+        if (theta_d + b.theta_o).min(PI) <= a.theta_o {
+            return LightCone { theta_e, ..a };
+        }
+
+        let theta_o = (a.theta_o + theta_d + b.theta_o) / 2.0;
+        let rotation_axis = Vec3::cross(&a.axis, &b.axis);
+        if theta_o >= PI || rotation_axis.length_squared() < 1e-12 {
+            return LightCone { axis: a.axis, theta_o: PI, theta_e };
+        }
+
+        // Turn the axis of a towards that of b.
+        let theta_r = theta_o - a.theta_o;
+        let towards_b = Vec3::cross(&Vec3::normalize(&rotation_axis), &a.axis);
+        LightCone {
+            axis: Vec3::normalize(&(theta_r.cos() * a.axis + theta_r.sin() * towards_b)),
+            theta_o,
+            theta_e,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Aabb,
+    cone: LightCone,
+    power: f64,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    // The index of the light.
+    Leaf(usize),
+    // The indices of the children.
+    Interior(usize, usize),
+}
+
+impl Node {
+    // An upper bound on the light the lights of the node give at p, up to a constant
+    // factor. The orientation of any surface at p is left out, so that it works for
+    // volumes too.
+    fn importance(&self, p: Pos3) -> f64 {
+        let centre = 0.5 * (self.bounds.min + self.bounds.max);
+        let radius = (self.bounds.max - centre).length();
+        let to_p = p - centre;
+        let d2 = to_p.length_squared().max(radius * radius);
+        if d2 == 0.0 {
+            return self.power;
+        }
+
+        // The angle between the axis and p, less the spread of the cone and the angle
+        // the bounds take up seen from p.
+        let theta = clamp(-1.0, 1.0, Vec3::dot(&self.cone.axis, &to_p) / d2.sqrt()).acos();
+        let theta_b = (radius / d2.sqrt()).min(1.0).asin();
+        let theta_min = (theta - self.cone.theta_o - theta_b).max(0.0);
+        if theta_min >= self.cone.theta_e {
+            return 0.0;
+        }
+
+        self.power * theta_min.cos() / d2
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LightBvh {
+    nodes: Vec<Node>,
+    // The parent of each node, and the leaf of each light, to find the probability of
+    // choosing a light from the bottom up.
+    parents: Vec<Option<usize>>,
+    leaves: Vec<Option<usize>>,
+}
+
+impl LightBvh {
+    // From the bounds, cones and powers of the lights, by index. Lights that have no
+    // bounds or are estimated to emit nothing are left out.
+    pub fn new(lights: &[Light]) -> LightBvh {
+        let mut leaves: Vec<Node> = lights
+            .iter()
+            .enumerate()
+            .filter_map(|(i, light)| {
+                let (bounds, cone) = light.bounds()?;
+                let power = light.power();
+                if power > 0.0 && power.is_finite() {
+                    Some(Node { bounds, cone, power, kind: NodeKind::Leaf(i) })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(2 * leaves.len());
+        if !leaves.is_empty() {
+            build(&mut leaves, &mut nodes);
+        }
+
+        let mut parents = vec![None; nodes.len()];
+        let mut leaves = vec![None; lights.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            match node.kind {
+                NodeKind::Leaf(light) => leaves[light] = Some(i),
+                NodeKind::Interior(left, right) => {
+                    parents[left] = Some(i);
+                    parents[right] = Some(i);
+                },
+            }
+        }
+
+        LightBvh { nodes, parents, leaves }
+    }
+
+    // A light with the probability of choosing it, None if no light can reach p.
+    pub fn sample(&self, p: Pos3) -> Option<(usize, f64)> {
+        let mut node = self.nodes.first()?;
+        let mut pmf = 1.0;
+
+        loop {
+            match node.kind {
+                NodeKind::Leaf(light) => return Some((light, pmf)),
+                NodeKind::Interior(left, right) => {
+                    let (left, right) = (&self.nodes[left], &self.nodes[right]);
+                    let (importance_left, importance_right) = (left.importance(p), right.importance(p));
+                    let total = importance_left + importance_right;
+                    if total <= 0.0 || !total.is_finite() {
+                        return None;
+                    }
+
+                    let p_left = importance_left / total;
+                    if random_zero_one() < p_left {
+                        node = left;
+                        pmf *= p_left;
+                    } else {
+                        node = right;
+                        pmf *= 1.0 - p_left;
+                    }
+                },
+            }
+        }
+    }
+
+    // The probability that sample chooses the light with index light at p.
+    pub fn pmf(&self, p: Pos3, light: usize) -> f64 {
+        let mut node = match self.leaves.get(light) {
+            Some(&Some(leaf)) => leaf,
+            _ => return 0.0,
+        };
+        let mut pmf = 1.0;
+
+        while let Some(parent) = self.parents[node] {
+            if let NodeKind::Interior(left, right) = self.nodes[parent].kind {
+                let (importance_left, importance_right) = (self.nodes[left].importance(p), self.nodes[right].importance(p));
+                let total = importance_left + importance_right;
+                if total <= 0.0 || !total.is_finite() {
+                    return 0.0;
+                }
+
+                let p_left = importance_left / total;
+                pmf *= if node == left { p_left } else { 1.0 - p_left };
+            }
+            node = parent;
+        }
+        pmf
+    }
+}
+
+// Builds the tree over leaves into nodes, returning the index of its root. Splits in the
+// middle of the lights along the axis their centres spread out the most along.
+fn build(leaves: &mut [Node], nodes: &mut Vec<Node>) -> usize {
+    if leaves.len() == 1 {
+        nodes.push(leaves[0].clone());
+        return nodes.len() - 1;
+    }
+
+    let centre = |node: &Node| 0.5 * (node.bounds.min + node.bounds.max);
+    let (lo, hi) = leaves.iter().fold((Vec3::from(INF), Vec3::from(-INF)), |(lo, hi), node| {
+        let c = centre(node);
+        (lo.zip_with(c, f64::min), hi.zip_with(c, f64::max))
+    });
+    let extent = hi - lo;
+    let axis = if extent.x > extent.y && extent.x > extent.z {
+        0
+    } else if extent.y > extent.z {
+        1
+    } else {
+        2
+    };
+    leaves.sort_by(|a, b| centre(a)[axis].partial_cmp(&centre(b)[axis]).unwrap());
+
+    // Reserve the slot of this node so that the root ends up first.
+    let index = nodes.len();
+    nodes.push(leaves[0].clone());
+    let (left, right) = leaves.split_at_mut(leaves.len() / 2);
+    let left = build(left, nodes);
+    let right = build(right, nodes);
+
+    let (l, r) = (&nodes[left], &nodes[right]);
+    nodes[index] = Node {
+        bounds: Aabb::surround(Some(l.bounds), Some(r.bounds)).unwrap(),
+        cone: LightCone::union(l.cone, r.cone),
+        power: l.power + r.power,
+        kind: NodeKind::Interior(left, right),
+    };
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::*;
+
+    fn bulb(centre: Pos3, radius: f64, brightness: f64) -> Light {
+        Light::Sphere(Sphere::new(centre, radius, Material::DiffuseLight {
+            emit: solid_colour(Colour::from(brightness)),
+            intensity: 1.0,
+            two_sided: false,
+            spot: None,
+            profile: None,
+        }))
+    }
+
+    #[test]
+    fn pmf_is_that_of_the_leaf() {
+        seed_rng(3);
+        let mut lights = Vec::new();
+        for i in 0..13 {
+            let t = i as f64;
+            let centre = Pos3::new(10.0 * (0.7 * t).sin(), t - 6.0, 4.0 * (1.3 * t).cos());
+            lights.push(bulb(centre, 0.1 + 0.05 * t, 1.0 + (t * 2.1) % 5.0));
+        }
+        lights.push(Light::Directional { direction: Vec3::new(0.0, -1.0, 0.0), irradiance: Colour::WHITE });
+        let bvh = LightBvh::new(&lights);
+
+        for p in &[Pos3::new(0.0, 0.0, 0.0), Pos3::new(8.0, -5.0, 2.0), Pos3::new(-30.0, 12.0, -1.0)] {
+            let sum: f64 = (0..lights.len()).map(|i| bvh.pmf(*p, i)).sum();
+            assert!((sum - 1.0).abs() < 1e-9, "pmf sums to {} at {:?}", sum, p);
+            assert_eq!(bvh.pmf(*p, lights.len() - 1), 0.0);
+
+            for _ in 0..100 {
+                let (i, pmf) = bvh.sample(*p).unwrap();
+                assert!((bvh.pmf(*p, i) - pmf).abs() < 1e-12, "light {}: {} against {}", i, bvh.pmf(*p, i), pmf);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Which lights to sample for the direct lighting at a point. With many lights sampling
+// all of them at every point is too slow, so one is chosen instead, and its light is
+// divided by the probability of choosing it. Lights at infinity are always sampled, being
+// few and lighting everything.
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum LightSampling {
+    All,
+    // In proportion to the power of each light.
+    Power,
+    // In proportion to an estimate of the light each gives at the point, see LightBvh.
+    #[default]
+    Bvh,
+}
+
+impl LightSampling {
+    pub fn from_name(name: &str) -> Option<LightSampling> {
+        match name {
+            "all" => Some(LightSampling::All),
+            "power" => Some(LightSampling::Power),
+            "bvh" => Some(LightSampling::Bvh),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LightSampling::All => "all",
+            LightSampling::Power => "power",
+            LightSampling::Bvh => "bvh",
+        }
+    }
+}
+
+pub struct LightSampler {
+    // Indices of the lights at infinity.
+    infinite: Vec<usize>,
+    // Over all lights, with those at infinity never chosen.
+    power: Option<AliasTable>,
+    bvh: LightBvh,
+}
+
+impl LightSampler {
+    pub fn new(lights: &[Light]) -> LightSampler {
+        let infinite: Vec<usize> = (0..lights.len()).filter(|&i| lights[i].bounds().is_none()).collect();
+        let powers: Vec<f64> = (0..lights.len())
+            .map(|i| if infinite.contains(&i) { 0.0 } else { lights[i].power() })
+            .collect();
+
+        LightSampler {
+            infinite,
+            power: AliasTable::new(&powers),
+            bvh: LightBvh::new(lights),
+        }
+    }
+
+    // The probability that for_each picks the light with index i at p.
+    pub fn pmf(&self, sampling: LightSampling, p: Pos3, i: usize) -> f64 {
+        if sampling == LightSampling::All || self.infinite.contains(&i) {
+            return 1.0;
+        }
+
+        match sampling {
+            LightSampling::Power => self.power.as_ref().map_or(0.0, |table| table.pmf(i)),
+            _ => self.bvh.pmf(p, i),
+        }
+    }
+
+    // Calls f with the index of each light sampled for the point p and the probability
+    // of having chosen it.
+    pub fn for_each(&self, lights: &[Light], sampling: LightSampling, p: Pos3, mut f: impl FnMut(usize, f64)) {
+        if sampling == LightSampling::All {
+            for i in 0..lights.len() {
+                f(i, 1.0);
+            }
+            return;
+        }
+
+        for &i in &self.infinite {
+            f(i, 1.0);
+        }
+
+        let chosen = match sampling {
+            LightSampling::Power => self.power.as_ref().map(|table| table.sample()),
+            _ => self.bvh.sample(p),
+        };
+        if let Some((i, pmf)) = chosen {
+            f(i, pmf);
+        }
+    }
+}
@@ -12,6 +12,18 @@ mod bvh;
 mod texture;
 mod perlin;
 mod hit;
+mod film;
+mod render;
+mod distributed;
+mod filter;
+mod onb;
+mod spectrum;
+mod medium;
+mod light;
+mod density;
+
+#[cfg(test)]
+mod golden_tests;
 
 pub use colour::*;
 pub use vec3::*;
@@ -27,61 +39,205 @@ pub use aabb::*;
 pub use bvh::*;
 pub use texture::*;
 pub use perlin::*;
+pub use film::*;
+pub use render::*;
+pub use distributed::*;
+pub use filter::*;
+pub use onb::*;
+pub use spectrum::*;
+pub use medium::*;
+pub use light::*;
+pub use density::*;
 
-fn main() {
-    let background = Colour::BLACK;
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::time::Duration;
 
-    eprintln!("Starting to build BVH.");
+struct Options {
+    settings: RenderSettings,
+    scene: String,
+    output: PathBuf,
+    worker: bool,
+    workers: Vec<WorkerCommand>,
+}
 
-    let (camera, objects) = final_scene_2(0.0, 1.0);
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        fn value<T: std::str::FromStr>(flag: &str, arg: Option<String>) -> Result<T, String> {
+            let arg = arg.ok_or_else(|| format!("Missing value for {}.", flag))?;
+            arg.parse().map_err(|_| format!("Invalid value for {}: {}", flag, arg))
+        }
 
-    eprintln!("Finished building BVH, starting actual ray tracing.");
+        fn values<T: std::str::FromStr>(flag: &str, arg: Option<String>) -> Result<[T; 4], String> {
+            let arg: String = value(flag, arg)?;
+            let vals = arg.split(',')
+                .map(|v| v.trim().parse().map_err(|_| format!("Invalid value for {}: {}", flag, arg)))
+                .collect::<Result<Vec<T>, _>>()?;
+            vals.try_into().map_err(|_| format!("Expected four comma separated values for {}: {}", flag, arg))
+        }
 
-    let buffer = Image::new(IMAGE_WIDTH, IMAGE_HEIGHT, |x, y| {
-        let col: Colour = (0..SAMPLES_PER_PIXEL)
-            .map(|_| {
-                let u = (x as f64 + random_zero_one()) / (IMAGE_WIDTH as f64 - 1.0);
-                let v = (y as f64 + random_zero_one()) / (IMAGE_HEIGHT as f64 - 1.0);
-                let ray = camera.get_ray(u, v);
-                debug_assert!(!ray.direction.is_nan());
-                let col = ray_colour(&objects, background, &ray, MAX_BOUNCES);
-                debug_assert!(!col.is_nan());
-                col
-            })
-            .sum();
+        let mut options = Options {
+            settings: RenderSettings::default(),
+            scene: "final_scene_2".to_string(),
+            output: PathBuf::from("out/image.png"),
+            worker: false,
+            workers: Vec::new(),
+        };
+        let mut filter_name: Option<String> = None;
+        let mut filter_radius: Option<f64> = None;
 
-        col / SAMPLES_PER_PIXEL as f64
-    });
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--scene" => options.scene = value(&flag, args.next())?,
+                "--width" => options.settings.width = value(&flag, args.next())?,
+                "--height" => options.settings.height = value(&flag, args.next())?,
+                "--samples" => options.settings.samples_per_pixel = value(&flag, args.next())?,
+                "--bounces" => options.settings.max_bounces = value(&flag, args.next())?,
+                "--samples-per-pass" => options.settings.samples_per_pass = value(&flag, args.next())?,
+                "--tile-size" => options.settings.tile_size = value(&flag, args.next())?,
+                "--time-budget" => {
+                    let seconds: f64 = value(&flag, args.next())?;
+                    options.settings.time_budget = Some(Duration::from_secs_f64(seconds));
+                },
+                "--seed" => options.settings.seed = value(&flag, args.next())?,
+                "--crop" => {
+                    let [x, y, width, height] = values(&flag, args.next())?;
+                    options.settings.crop = Some(Crop::Pixels(PixelRect::new(x, y, width, height)));
+                },
+                "--crop-normalised" => {
+                    let [x0, y0, x1, y1] = values(&flag, args.next())?;
+                    options.settings.crop = Some(Crop::Normalised { x0, y0, x1, y1 });
+                },
+                "--crop-full-frame" => options.settings.crop_full_frame = true,
+                "--filter" => filter_name = Some(value(&flag, args.next())?),
+                "--filter-radius" => filter_radius = Some(value(&flag, args.next())?),
+                "--light-sampling" => {
+                    let name: String = value(&flag, args.next())?;
+                    options.settings.light_sampling = LightSampling::from_name(&name)
+                        .ok_or_else(|| format!("Unknown light sampling: {}", name))?;
+                },
+                "--output" => options.output = value(&flag, args.next())?,
+                "--worker" => options.worker = true,
+                "--workers" => {
+                    let n: usize = value(&flag, args.next())?;
+                    options.workers.extend((0..n).map(|_| local_worker_command()));
+                },
+                "--remote-worker" => {
+                    let command: String = value(&flag, args.next())?;
+                    options.workers.push(command.split_whitespace().map(String::from).collect());
+                },
+                _ => return Err(format!("Unknown argument: {}", flag)),
+            }
+        }
 
-    eprintln!("Raytracing done, saving PNG to disk.");
+        if !SCENE_NAMES.contains(&options.scene.as_str()) {
+            return Err(format!("Unknown scene: {}, expected one of {}", options.scene, SCENE_NAMES.join(", ")));
+        }
+
+        if filter_name.is_some() || filter_radius.is_some() {
+            let name = filter_name.unwrap_or_else(|| options.settings.filter.name().to_string());
+            options.settings.filter = Filter::from_name(&name, filter_radius)
+                .ok_or_else(|| format!("Unknown filter: {}", name))?;
+        }
 
-    buffer.save();
+        if options.settings.crop_rect().area() == 0 {
+            return Err("Nothing to render, the crop window lies outside the image.".to_string());
+        }
 
-    // eprintln!("PPM saved, converting to PNG.");
+        Ok(options)
+    }
 
-    // let img = ext_image::open(Path::new("./out/image.ppm")).expect("Failed reading PPM-file.");
+    // The arguments a worker needs to render the same image as the coordinator.
+    fn worker_args(&self) -> Vec<String> {
+        let s = &self.settings;
+        vec![
+            "--worker".to_string(),
+            "--scene".to_string(), self.scene.clone(),
+            "--width".to_string(), s.width.to_string(),
+            "--height".to_string(), s.height.to_string(),
+            "--samples".to_string(), s.samples_per_pixel.to_string(),
+            "--bounces".to_string(), s.max_bounces.to_string(),
+            "--seed".to_string(), s.seed.to_string(),
+            "--filter".to_string(), s.filter.name().to_string(),
+            "--filter-radius".to_string(), s.filter.radius().to_string(),
+            "--light-sampling".to_string(), s.light_sampling.name().to_string(),
+        ]
+    }
+}
 
-    // img.save(Path::new("./out/image.png")).expect("Failed writing PNG.");
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let settings = options.settings;
+    let cancel = CancelToken::new();
+    cancel_on_interrupt(&cancel, options.worker);
+
+    if options.worker {
+        let scene = build_scene(&options.scene, settings.seed).unwrap();
+        if let Err(e) = run_worker(&scene, &settings) {
+            eprintln!("Worker failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let film = if options.workers.is_empty() {
+        eprintln!("Starting to build BVH.");
+
+        let scene = build_scene(&options.scene, settings.seed).unwrap();
+
+        eprintln!("Finished building BVH, starting actual ray tracing.");
+
+        render(&scene, &settings, &cancel)
+    } else {
+        eprintln!("Starting distributed ray tracing with {} workers.", options.workers.len());
+
+        render_distributed(&settings, &options.workers, &options.worker_args(), &cancel).unwrap_or_else(|e| {
+            eprintln!("Distributed rendering failed: {}", e);
+            std::process::exit(1);
+        })
+    };
+
+    eprintln!("Raytracing done, saving PNG to disk.");
+
+    Image::from_film(&film).save_to(&options.output);
 
     eprintln!("Done!");
 }
 
-#[allow(clippy::ptr_arg)]
-fn ray_colour(world: &Objects, background_colour: Colour, ray: &Ray, depth: usize) -> Colour {
-    if depth == 0 {
-        return Colour::BLACK;
-    }
-    if let Some(hr) = world.hit(&ray, 0.001, INF) {
-        if let Some((new_ray, attenuation)) = hr.material.scatter(ray, &hr) {
-            let res = ray_colour(world, background_colour, &new_ray, depth - 1);
-            let emitted = hr.material.emit(hr.u, hr.v, hr.p);
-            let col = emitted + attenuation * res;
-            debug_assert!(!col.is_nan());
-            col
-        } else {
-            hr.material.emit(hr.u, hr.v, hr.p)
+// Makes Ctrl-C stop the render between tiles, after which what has been rendered so far
+// is saved as usual. A second Ctrl-C kills it straight away. Workers ignore it, as the
+// terminal sends it to them too, and finish their tile for the coordinator instead.
+#[cfg(unix)]
+fn cancel_on_interrupt(cancel: &CancelToken, worker: bool) {
+    use std::sync::OnceLock;
+
+    static CANCEL: OnceLock<CancelToken> = OnceLock::new();
+
+    extern "C" fn interrupted(_: libc::c_int) {
+        if let Some(cancel) = CANCEL.get() {
+            cancel.cancel();
         }
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
+
+    let handler = if worker {
+        libc::SIG_IGN
     } else {
-        background_colour
+        let _ = CANCEL.set(cancel.clone());
+        interrupted as extern "C" fn(libc::c_int) as libc::sighandler_t
+    };
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
     }
 }
+
+#[cfg(not(unix))]
+fn cancel_on_interrupt(_cancel: &CancelToken, _worker: bool) {}
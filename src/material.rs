@@ -4,87 +4,791 @@ use crate::hit::*;
 use crate::vec3::*;
 use crate::utility::*;
 use crate::texture::*;
+use crate::onb::*;
+use crate::spectrum::*;
+use crate::medium::*;
+use crate::light::*;
+use crate::density::*;
+
+use std::sync::Arc;
+
+pub mod microfacet;
+pub mod dispersion;
+pub mod principled;
+pub mod diffuse;
+pub mod bump;
+pub mod thin_film;
+pub mod sheen;
+pub mod emission;
+
+pub use microfacet::*;
+pub use dispersion::*;
+pub use principled::*;
+pub use diffuse::*;
+pub use bump::*;
+pub use thin_film::*;
+pub use sheen::*;
+pub use emission::*;
 
 #[derive(Clone)]
 pub enum Material {
     Lambertian {
         albedo: Texture,
     },
+    // A rough diffuse surface, e.g. clay or concrete, sigma being the standard deviation
+    // of the angle of its facets in radians.
+    OrenNayar {
+        albedo: Texture,
+        sigma: ScalarTexture,
+    },
     Metal {
-        albedo: Colour,
-        fuzziness: f64,
+        albedo: Texture,
+        fuzziness: ScalarTexture,
     },
+    // Absorption is the absorption coefficient per channel, so light travelling a
+    // distance d inside the material is left with exp(-absorption * d) of itself, by the
+    // Beer-Lambert law.
     Dielectric {
-        refractive_index: f64,
+        refractive_index: ScalarTexture,
+        absorption: Texture,
+    },
+    // Glass with a GGX microfacet distribution, e.g. frosted glass.
+    RoughDielectric {
+        refractive_index: ScalarTexture,
+        roughness: ScalarTexture,
+        absorption: Texture,
+    },
+    // Glass whose refractive index depends on the wavelength, see Dispersion for presets.
+    // Paths through it continue at a single wavelength. Unlike the other parameters the
+    // dispersion can't be a texture, being a curve rather than a value; glass that varies
+    // from place to place can be made of several objects instead.
+    DispersiveDielectric {
+        dispersion: Dispersion,
+        roughness: ScalarTexture,
+        absorption: Texture,
+    },
+    // A dielectric boundary around a scattering medium, e.g. wax, marble, milk or skin.
+    // Light refracted into it random walks through the medium until it gets back out.
+    Subsurface {
+        refractive_index: ScalarTexture,
+        roughness: ScalarTexture,
+        medium: Medium,
+    },
+    // A rough metal with a GGX microfacet distribution. Eta and k are the real and
+    // imaginary parts of its index of refraction per channel, see ComplexIor for presets.
+    Conductor {
+        eta: Texture,
+        k: Texture,
+        roughness: ScalarTexture,
+    },
+    // A dielectric coat over another material, e.g. car paint or lacquered wood.
+    // Absorption is the optical depth of the coat, per channel, for light crossing it
+    // straight on.
+    Layered {
+        base: Box<Material>,
+        refractive_index: ScalarTexture,
+        roughness: ScalarTexture,
+        absorption: Texture,
+    },
+    // A thin film with its thickness in nanometres, reflecting colours that change with
+    // the view, over another material. Without one, the film is all there is, e.g. a soap
+    // bubble.
+    ThinFilm {
+        base: Option<Box<Material>>,
+        thickness: ScalarTexture,
+        refractive_index: ScalarTexture,
     },
+    // A sheen over another material, e.g. velvet. Without one, only the sheen reflects.
+    Sheen {
+        base: Option<Box<Material>>,
+        colour: Texture,
+        roughness: ScalarTexture,
+    },
+    // Either of two materials, the second with the probability given by the mask at the
+    // hit, which on average blends them. A hard mask picks one or the other, e.g. rust
+    // patches on metal.
+    Mix {
+        first: Box<Material>,
+        second: Box<Material>,
+        mask: ScalarTexture,
+    },
+    // Another material with its normal displaced by a height map, in world units.
+    BumpMap {
+        base: Box<Material>,
+        height: ScalarTexture,
+    },
+    // Another material with its normal from a tangent space normal map, see mapped_normal.
+    NormalMap {
+        base: Box<Material>,
+        normals: Texture,
+    },
+    // Lets rays through unchanged, see Material::cutout.
+    Transparent,
+    // Boxed, being a lot larger than the other variants.
+    Principled(Box<Principled>),
+    // Emits its texture times the intensity. A one-sided light only emits from its
+    // outside, e.g. from the side of a rect its normal points to. With a profile the
+    // intensity of each part of the surface follows that of the fixture, its nadir along
+    // the normal and its horizontal angle 0 along dpdu, rather than the cosine law.
     DiffuseLight {
         emit: Texture,
+        intensity: f64,
+        two_sided: bool,
+        spot: Option<Spot>,
+        profile: Option<Arc<IesProfile>>,
     },
     Isotropic {
         albedo: Texture,
     },
+    // A medium that scatters preferentially forwards or backwards, see HenyeyGreenstein.
+    Anisotropic {
+        albedo: Texture,
+        phase: HenyeyGreenstein,
+    },
+    // A medium that glows, e.g. fire, and otherwise scatters like base. Each collision
+    // in the medium adds the emission at it, taken in the space of the medium, so along
+    // a ray the light adds up in proportion to the density as well.
+    EmissiveMedium {
+        base: Box<Material>,
+        emission: EmissionField,
+    },
 }
 
 impl Material {
-    pub fn scatter(&self, ray: &Ray, hr: &HitRecord) -> Option<(Ray, Colour)> {
+    // The material where alpha is 1, with holes where it is 0 that rays pass through.
+    pub fn cutout(material: Material, alpha: ScalarTexture) -> Material {
+        Material::Mix {
+            first: Box::new(Material::Transparent),
+            second: Box::new(material),
+            mask: alpha,
+        }
+    }
+
+    // The next ray of a path and its weight, and whether the ray went straight through,
+    // as through the hole of a cutout, in which case shadow rays get through as well.
+    pub fn scatter(&self, ray: &Ray, hr: &HitRecord) -> Option<(Ray, Colour, bool)> {
         match self {
             Material::Lambertian { albedo } => {
                 let scatter_direction = hr.normal + random_unit_vec();
-                let scattered = Ray::new(hr.p, scatter_direction, ray.time);
+                let scattered = ray.spawn(hr.p, scatter_direction);
                 let attenuation = albedo(hr.u, hr.v, hr.p);
 
-                Some((scattered, attenuation))
+                Some((scattered, attenuation, false))
+            },
+
+            Material::OrenNayar { albedo, sigma } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let (wi, weight) = sample_oren_nayar(wo, sigma(hr.u, hr.v, hr.p));
+
+                Some((ray.spawn(hr.p, onb.to_world(wi)), weight * albedo(hr.u, hr.v, hr.p), false))
             },
 
             Material::Metal { albedo, fuzziness } => {
                 let reflected = ray.direction.reflect(hr.normal);
-                let scattered = Ray::new(hr.p, reflected + *fuzziness * random_vec_in_unit_sphere(), ray.time);
+                let fuzziness = fuzziness(hr.u, hr.v, hr.p);
+                let scattered = ray.spawn(hr.p, reflected + fuzziness * random_vec_in_unit_sphere());
 
-                Some((scattered, *albedo))
+                Some((scattered, albedo(hr.u, hr.v, hr.p), false))
             },
 
-            Material::Dielectric { refractive_index } => {
+            Material::Dielectric { refractive_index, absorption } => {
+                let refractive_index = refractive_index(hr.u, hr.v, hr.p);
                 let (normal, eta_over_eta) = match hr.side {
                     Side::Outside => (hr.normal, 1.0 / refractive_index),
-                    Side::Inside => (-hr.normal, *refractive_index),
+                    Side::Inside => (-hr.normal, refractive_index),
                 };
 
                 let cos_theta = min(Vec3::dot(&-Vec3::normalize(&ray.direction), &normal), 1.0);
                 
-                let ray = if schlick(cos_theta, eta_over_eta) > random_zero_one() {
-                    Ray::new(hr.p, Vec3::normalize(&ray.direction).reflect(normal), ray.time)
+                let scattered = if schlick(cos_theta, eta_over_eta) > random_zero_one() {
+                    ray.spawn(hr.p, Vec3::normalize(&ray.direction).reflect(normal))
                 } else if let Some(refracted) = ray.direction.refract(eta_over_eta, normal) {
-                    Ray::new(hr.p, refracted, ray.time)
+                    ray.spawn(hr.p, refracted)
                 } else {
-                    Ray::new(hr.p, Vec3::normalize(&ray.direction).reflect(normal), ray.time)
+                    ray.spawn(hr.p, Vec3::normalize(&ray.direction).reflect(normal))
+                };
+
+                Some((scattered, beer_lambert(absorption, ray, hr), false))
+            },
+            Material::RoughDielectric { refractive_index, roughness, absorption } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let refractive_index = refractive_index(hr.u, hr.v, hr.p);
+                let eta = match hr.side {
+                    Side::Outside => refractive_index,
+                    Side::Inside => 1.0 / refractive_index,
+                };
+
+                sample_dielectric(wo, eta, roughness(hr.u, hr.v, hr.p)).map(|(wi, weight)|
+                    (ray.spawn(hr.p, onb.to_world(wi)), weight * beer_lambert(absorption, ray, hr), false)
+                )
+            },
+            Material::DispersiveDielectric { dispersion, roughness, absorption } => {
+                let (wavelength, weight) = match ray.wavelength {
+                    Some(wavelength) => (wavelength, Colour::WHITE),
+                    None => sample_wavelength(),
+                };
+
+                let (onb, wo) = shading_frame(ray, hr);
+                let refractive_index = dispersion.refractive_index(wavelength);
+                let eta = match hr.side {
+                    Side::Outside => refractive_index,
+                    Side::Inside => 1.0 / refractive_index,
                 };
 
-                Some((ray, Colour::from(1.0)))
+                sample_dielectric(wo, eta, roughness(hr.u, hr.v, hr.p)).map(|(wi, g)| {
+                    let scattered = Ray {
+                        wavelength: Some(wavelength),
+                        ..ray.spawn(hr.p, onb.to_world(wi))
+                    };
+                    (scattered, weight * g * beer_lambert(absorption, ray, hr), false)
+                })
+            },
+            Material::Subsurface { refractive_index, roughness, medium } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let refractive_index = refractive_index(hr.u, hr.v, hr.p);
+                let eta = match hr.side {
+                    Side::Outside => refractive_index,
+                    Side::Inside => 1.0 / refractive_index,
+                };
+
+                sample_dielectric(wo, eta, roughness(hr.u, hr.v, hr.p)).map(|(wi, weight)| {
+                    let mut scattered = ray.spawn(hr.p, onb.to_world(wi));
+                    if wi.z < 0.0 {
+                        scattered.medium = match hr.side {
+                            Side::Outside => Some(*medium),
+                            Side::Inside => None,
+                        };
+                    }
+                    (scattered, Colour::from(weight), false)
+                })
+            },
+            Material::Conductor { eta, k, roughness } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let ior = ComplexIor::new(eta(hr.u, hr.v, hr.p), k(hr.u, hr.v, hr.p));
+
+                sample_conductor(wo, &ior, roughness(hr.u, hr.v, hr.p)).map(|(wi, weight)|
+                    (ray.spawn(hr.p, onb.to_world(wi)), weight, false)
+                )
+            },
+            Material::Principled(principled) => {
+                let (onb, wo) = shading_frame(ray, hr);
+
+                principled.sample(wo, hr.side, hr.u, hr.v, hr.p).map(|(wi, weight)|
+                    (ray.spawn(hr.p, onb.to_world(wi)), weight, false)
+                )
+            },
+            Material::Layered { base, refractive_index, roughness, absorption } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let refractive_index = refractive_index(hr.u, hr.v, hr.p);
+                let roughness = roughness(hr.u, hr.v, hr.p);
+                let absorption = absorption(hr.u, hr.v, hr.p);
+
+                let (wi, weight) = sample_dielectric(wo, refractive_index, roughness)?;
+                if wi.z > 0.0 {
+                    return Some((ray.spawn(hr.p, onb.to_world(wi)), Colour::from(weight), false));
+                }
+
+                // Refracted into the coat, bounce between the base and the underside of the
+                // coat until getting back out.
+                let base_hr = HitRecord {
+                    normal: onb.w,
+                    side: Side::Outside,
+                    ..*hr
+                };
+                let mut inner = ray.spawn(hr.p, onb.to_world(wi));
+                let mut throughput = weight * coat_transmittance(absorption, wi);
+
+                for _ in 0..MAX_COAT_BOUNCES {
+                    let (scattered, attenuation, _) = base.scatter(&inner, &base_hr)?;
+                    throughput = throughput * attenuation;
+
+                    let up = onb.to_local(Vec3::normalize(&scattered.direction));
+                    if up.z <= 0.0 {
+                        // Through the base, e.g. glass.
+                        return Some((scattered, throughput, false));
+                    }
+                    throughput = throughput * coat_transmittance(absorption, up);
+
+                    // The coat seen from below, mirrored to be seen from above.
+                    let (wi, weight) = sample_dielectric(Vec3::new(-up.x, -up.y, up.z), 1.0 / refractive_index, roughness)?;
+                    let wi = Vec3::new(wi.x, wi.y, -wi.z);
+                    throughput *= weight;
+
+                    let next = scattered.spawn(hr.p, onb.to_world(wi));
+                    if wi.z > 0.0 {
+                        return Some((next, throughput, false));
+                    }
+                    throughput = throughput * coat_transmittance(absorption, wi);
+                    inner = next;
+                }
+
+                None
+            },
+            Material::ThinFilm { base, thickness, refractive_index } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let reflectance = film_reflectance(ray, hr, base.as_deref(), thickness, refractive_index);
+
+                let p = clamp(0.0, 1.0, (reflectance.r + reflectance.g + reflectance.b) / 3.0);
+                if random_zero_one() < p {
+                    let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+                    return Some((ray.spawn(hr.p, onb.to_world(wi)), reflectance / p, false));
+                }
+
+                let transmittance = reflectance.map(|r| 1.0 - r) / (1.0 - p);
+                match base {
+                    Some(base) => base.scatter(ray, hr).map(|(scattered, weight, through)| (scattered, weight * transmittance, through)),
+                    None => Some((ray.spawn(hr.p, ray.direction), transmittance, true)),
+                }
+            },
+            Material::Sheen { base, colour, roughness } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let colour = colour(hr.u, hr.v, hr.p);
+                let roughness = roughness(hr.u, hr.v, hr.p);
+
+                // The sheen reflects its albedo, the base gets the rest.
+                let albedo = colour * sheen_albedo(wo.z, roughness);
+                let p = clamp(0.0, 1.0, (albedo.r + albedo.g + albedo.b) / 3.0);
+                if random_zero_one() < p {
+                    // Cosine sampled, so the weight is f * pi.
+                    let wi = random_cosine_direction();
+                    return Some((ray.spawn(hr.p, onb.to_world(wi)), colour * (PI * sheen_brdf(wo, wi, roughness) / p), false));
+                }
+
+                let rest = albedo.map(|a| 1.0 - a) / (1.0 - p);
+                base.as_ref()?.scatter(ray, hr).map(|(scattered, weight, through)| (scattered, weight * rest, through))
+            },
+            Material::Mix { first, second, mask } => {
+                if random_zero_one() < clamp(0.0, 1.0, mask(hr.u, hr.v, hr.p)) {
+                    second.scatter(ray, hr)
+                } else {
+                    first.scatter(ray, hr)
+                }
+            },
+            Material::BumpMap { base, height } => {
+                base.scatter(ray, &HitRecord { normal: bump_normal(hr, height), ..*hr })
+            },
+            Material::NormalMap { base, normals } => {
+                base.scatter(ray, &HitRecord { normal: mapped_normal(hr, normals), ..*hr })
+            },
+            Material::Transparent => {
+                Some((ray.spawn(hr.p, ray.direction), Colour::WHITE, true))
             },
             Material::DiffuseLight { .. } => {
                 None
             },
             Material::Isotropic { albedo } => {
-                let ray = Ray::new(
-                    hr.p, 
-                    random_vec_in_unit_sphere(),
-                    ray.time
-                );
+                let ray = ray.spawn(hr.p, random_vec_in_unit_sphere());
                 let colour = albedo(hr.u, hr.v, hr.p);
                 
-                Some((ray, colour))
+                Some((ray, colour, false))
+            },
+            Material::Anisotropic { albedo, phase } => {
+                let scattered = ray.spawn(hr.p, phase.sample(Vec3::normalize(&ray.direction)));
+
+                Some((scattered, albedo(hr.u, hr.v, hr.p), false))
+            },
+            Material::EmissiveMedium { base, .. } => {
+                base.scatter(ray, hr)
             },
         }
     }
 
-    pub fn emit(&self, u: f64, v: f64, p: Pos3) -> Colour {
+    // The light reflected back along the ray from light arriving from direction wi, a unit
+    // vector away from the surface, per unit of irradiance, i.e. the BSDF times the cosine
+    // of wi. Black for lobes that are specular and so can't reflect light from any one
+    // direction, those only pick up light by scattering.
+    pub fn eval(&self, ray: &Ray, hr: &HitRecord, wi: Vec3) -> Colour {
         match self {
-            Material::DiffuseLight { emit } => {
-                emit(u, v, p)
+            Material::Lambertian { albedo } => {
+                let (onb, _) = shading_frame(ray, hr);
+                let cos = onb.to_local(wi).z;
+                if cos <= 0.0 {
+                    return Colour::BLACK;
+                }
+
+                albedo(hr.u, hr.v, hr.p) * (cos / PI)
+            },
+            Material::OrenNayar { albedo, sigma } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let wi = onb.to_local(wi);
+                if wi.z <= 0.0 {
+                    return Colour::BLACK;
+                }
+
+                albedo(hr.u, hr.v, hr.p) * (oren_nayar(wo, wi, sigma(hr.u, hr.v, hr.p)) * wi.z / PI)
+            },
+            Material::Conductor { eta, k, roughness } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let ior = ComplexIor::new(eta(hr.u, hr.v, hr.p), k(hr.u, hr.v, hr.p));
+
+                eval_conductor(wo, onb.to_local(wi), &ior, roughness(hr.u, hr.v, hr.p))
+            },
+            Material::Principled(principled) => {
+                let (onb, wo) = shading_frame(ray, hr);
+
+                principled.eval(wo, onb.to_local(wi), hr.side, hr.u, hr.v, hr.p)
+            },
+            Material::Layered { base, refractive_index, roughness, absorption } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let local = onb.to_local(wi);
+                if local.z <= 0.0 {
+                    return Colour::BLACK;
+                }
+                let refractive_index = refractive_index(hr.u, hr.v, hr.p);
+                let roughness = roughness(hr.u, hr.v, hr.p);
+                let absorption = absorption(hr.u, hr.v, hr.p);
+
+                let coat = match ggx_reflection(wo, local, roughness_to_alpha(roughness)) {
+                    Some((h, f)) if !is_smooth(roughness) => fresnel_dielectric(Vec3::dot(&wo, &h), refractive_index) * f * local.z,
+                    _ => 0.0,
+                };
+
+                // Light through the coat onto the base and back, ignoring the bending of
+                // its direction and any bounces under the coat.
+                let base_hr = HitRecord {
+                    normal: onb.w,
+                    side: Side::Outside,
+                    ..*hr
+                };
+                let through = (1.0 - fresnel_dielectric(wo.z, refractive_index)) * (1.0 - fresnel_dielectric(local.z, refractive_index));
+                let under = base.eval(ray, &base_hr, wi) * coat_transmittance(absorption, wo) * coat_transmittance(absorption, local);
+
+                Colour::from(coat) + through * under
+            },
+            Material::ThinFilm { base: Some(base), thickness, refractive_index } => {
+                let reflectance = film_reflectance(ray, hr, Some(base), thickness, refractive_index);
+                reflectance.map(|r| 1.0 - r) * base.eval(ray, hr, wi)
+            },
+            Material::Sheen { base, colour, roughness } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let local = onb.to_local(wi);
+                let colour = colour(hr.u, hr.v, hr.p);
+                let roughness = roughness(hr.u, hr.v, hr.p);
+
+                let sheen = colour * (sheen_brdf(wo, local, roughness) * local.z.max(0.0));
+                let rest = (colour * sheen_albedo(wo.z, roughness)).map(|a| 1.0 - a);
+                match base {
+                    Some(base) => sheen + rest * base.eval(ray, hr, wi),
+                    None => sheen,
+                }
+            },
+            Material::Mix { first, second, mask } => {
+                Colour::col_lerp(first.eval(ray, hr, wi), second.eval(ray, hr, wi), clamp(0.0, 1.0, mask(hr.u, hr.v, hr.p)))
+            },
+            Material::BumpMap { base, height } => {
+                base.eval(ray, &HitRecord { normal: bump_normal(hr, height), ..*hr }, wi)
+            },
+            Material::NormalMap { base, normals } => {
+                base.eval(ray, &HitRecord { normal: mapped_normal(hr, normals), ..*hr }, wi)
+            },
+            Material::Isotropic { albedo } => {
+                albedo(hr.u, hr.v, hr.p) / (4.0 * PI)
+            },
+            Material::Anisotropic { albedo, phase } => {
+                albedo(hr.u, hr.v, hr.p) * phase.eval(Vec3::dot(&Vec3::normalize(&ray.direction), &wi))
+            },
+            Material::EmissiveMedium { base, .. } => {
+                base.eval(ray, hr, wi)
+            },
+            _ => {
+                Colour::BLACK
+            },
+        }
+    }
+
+    // The probability density of scatter picking the unit direction wi, for the lobes
+    // that eval accounts for. Materials that don't know theirs give 0, leaving light from
+    // emitters to be sampled.
+    pub fn pdf(&self, ray: &Ray, hr: &HitRecord, wi: Vec3) -> f64 {
+        match self {
+            Material::Lambertian { .. } | Material::OrenNayar { .. } => {
+                let (onb, _) = shading_frame(ray, hr);
+                onb.to_local(wi).z.max(0.0) / PI
+            },
+            Material::Conductor { roughness, .. } => {
+                let roughness = roughness(hr.u, hr.v, hr.p);
+                if is_smooth(roughness) {
+                    return 0.0;
+                }
+                let (onb, wo) = shading_frame(ray, hr);
+
+                ggx_reflection_pdf(wo, onb.to_local(wi), roughness_to_alpha(roughness))
+            },
+            Material::Principled(principled) => {
+                let (onb, wo) = shading_frame(ray, hr);
+
+                principled.pdf(wo, onb.to_local(wi), hr.side, hr.u, hr.v, hr.p)
+            },
+            Material::Sheen { base, colour, roughness } => {
+                let (onb, wo) = shading_frame(ray, hr);
+                let albedo = colour(hr.u, hr.v, hr.p) * sheen_albedo(wo.z, roughness(hr.u, hr.v, hr.p));
+                let p = clamp(0.0, 1.0, (albedo.r + albedo.g + albedo.b) / 3.0);
+                let base_pdf = base.as_ref().map_or(0.0, |base| base.pdf(ray, hr, wi));
+
+                p * onb.to_local(wi).z.max(0.0) / PI + (1.0 - p) * base_pdf
+            },
+            Material::Mix { first, second, mask } => {
+                let t = clamp(0.0, 1.0, mask(hr.u, hr.v, hr.p));
+                (1.0 - t) * first.pdf(ray, hr, wi) + t * second.pdf(ray, hr, wi)
+            },
+            Material::BumpMap { base, height } => {
+                base.pdf(ray, &HitRecord { normal: bump_normal(hr, height), ..*hr }, wi)
+            },
+            Material::NormalMap { base, normals } => {
+                base.pdf(ray, &HitRecord { normal: mapped_normal(hr, normals), ..*hr }, wi)
+            },
+            Material::Isotropic { .. } => {
+                1.0 / (4.0 * PI)
+            },
+            Material::Anisotropic { phase, .. } => {
+                phase.eval(Vec3::dot(&Vec3::normalize(&ray.direction), &wi))
+            },
+            Material::EmissiveMedium { base, .. } => {
+                base.pdf(ray, hr, wi)
+            },
+            _ => {
+                0.0
+            },
+        }
+    }
+
+    // Whether scatter can pick directions that eval doesn't account for, e.g. those of
+    // perfect reflections, in which case light from emitters has to be picked up by
+    // hitting them rather than by sampling them.
+    pub fn has_specular(&self, hr: &HitRecord) -> bool {
+        match self {
+            Material::Lambertian { .. }
+            | Material::OrenNayar { .. }
+            | Material::Isotropic { .. }
+            | Material::Anisotropic { .. }
+            | Material::DiffuseLight { .. } => {
+                false
+            },
+            Material::Conductor { roughness, .. } => {
+                is_smooth(roughness(hr.u, hr.v, hr.p))
+            },
+            Material::Principled(principled) => {
+                principled.has_specular(hr.u, hr.v, hr.p)
+            },
+            Material::Sheen { base, .. } => {
+                base.as_ref().is_some_and(|base| base.has_specular(hr))
+            },
+            Material::Mix { first, second, .. } => {
+                first.has_specular(hr) || second.has_specular(hr)
+            },
+            Material::BumpMap { base, .. }
+            | Material::NormalMap { base, .. }
+            | Material::EmissiveMedium { base, .. } => {
+                base.has_specular(hr)
+            },
+            _ => {
+                true
+            },
+        }
+    }
+
+    // The real and imaginary part of the index of refraction at a wavelength just below
+    // the surface, for a thin film over it. Materials without one, e.g. diffuse ones, are
+    // as if there was air under the film.
+    pub fn ior_at(&self, hr: &HitRecord, wavelength: f64) -> (f64, f64) {
+        match self {
+            Material::Dielectric { refractive_index, .. }
+            | Material::RoughDielectric { refractive_index, .. }
+            | Material::Subsurface { refractive_index, .. }
+            | Material::Layered { refractive_index, .. }
+            | Material::ThinFilm { refractive_index, .. } => {
+                (refractive_index(hr.u, hr.v, hr.p), 0.0)
+            },
+            Material::DispersiveDielectric { dispersion, .. } => {
+                (dispersion.refractive_index(wavelength), 0.0)
+            },
+            Material::Conductor { eta, k, .. } => {
+                ComplexIor::new(eta(hr.u, hr.v, hr.p), k(hr.u, hr.v, hr.p)).at(wavelength)
+            },
+            Material::Sheen { base: Some(base), .. }
+            | Material::BumpMap { base, .. }
+            | Material::NormalMap { base, .. } => {
+                base.ior_at(hr, wavelength)
+            },
+            _ => {
+                (1.0, 0.0)
+            },
+        }
+    }
+
+    // How much of the light going straight through the surface along the ray it stops,
+    // for shadow rays. That is the chance of scatter not passing straight through, so
+    // cutouts and bare films let light through, all else stops it.
+    pub fn opacity(&self, ray: &Ray, hr: &HitRecord) -> f64 {
+        match self {
+            Material::Transparent => {
+                0.0
+            },
+            Material::Mix { first, second, mask } => {
+                let t = clamp(0.0, 1.0, mask(hr.u, hr.v, hr.p));
+                (1.0 - t) * first.opacity(ray, hr) + t * second.opacity(ray, hr)
+            },
+            Material::ThinFilm { base, thickness, refractive_index } => {
+                let reflectance = film_reflectance(ray, hr, base.as_deref(), thickness, refractive_index);
+                let p = clamp(0.0, 1.0, (reflectance.r + reflectance.g + reflectance.b) / 3.0);
+                let base_opacity = base.as_ref().map_or(0.0, |base| base.opacity(ray, hr));
+
+                1.0 - (1.0 - p) * (1.0 - base_opacity)
+            },
+            Material::Sheen { base: Some(base), colour, roughness } => {
+                let (_, wo) = shading_frame(ray, hr);
+                let albedo = colour(hr.u, hr.v, hr.p) * sheen_albedo(wo.z, roughness(hr.u, hr.v, hr.p));
+                let p = clamp(0.0, 1.0, (albedo.r + albedo.g + albedo.b) / 3.0);
+
+                1.0 - (1.0 - p) * (1.0 - base.opacity(ray, hr))
+            },
+            Material::BumpMap { base, .. }
+            | Material::NormalMap { base, .. }
+            | Material::EmissiveMedium { base, .. } => {
+                base.opacity(ray, hr)
+            },
+            _ => {
+                1.0
+            },
+        }
+    }
+
+    // The light emitted back along the ray.
+    pub fn emit(&self, ray: &Ray, hr: &HitRecord) -> Colour {
+        match self {
+            Material::DiffuseLight { emit, intensity, two_sided, spot, profile } => {
+                if !two_sided && hr.side == Side::Inside {
+                    return Colour::BLACK;
+                }
+
+                let out = -Vec3::normalize(&ray.direction);
+                let cos = Vec3::dot(&out, &hr.normal).abs();
+                let mut falloff = match spot {
+                    Some(spot) => spot.falloff(cos),
+                    None => 1.0,
+                };
+                if let Some(profile) = profile {
+                    // Radiance is intensity per projected area, so undo the cosine, up to
+                    // a limit at grazing angles.
+                    let normal = if Vec3::dot(&out, &hr.normal) < 0.0 { -hr.normal } else { hr.normal };
+                    let local = IesProfile::frame(normal, hr.dpdu).to_local(out);
+                    falloff *= profile.intensity(local) / cos.max(PROFILE_MIN_COS);
+                }
+
+                *intensity * falloff * emit(hr.u, hr.v, hr.p)
+            },
+            Material::Mix { first, second, mask } => {
+                Colour::col_lerp(first.emit(ray, hr), second.emit(ray, hr), clamp(0.0, 1.0, mask(hr.u, hr.v, hr.p)))
+            },
+            Material::BumpMap { base, .. } | Material::NormalMap { base, .. } => {
+                base.emit(ray, hr)
+            },
+            Material::ThinFilm { base: Some(base), .. } | Material::Sheen { base: Some(base), .. } => {
+                base.emit(ray, hr)
+            },
+            Material::EmissiveMedium { base, emission } => {
+                emission(hr.local) + base.emit(ray, hr)
             },
             _ => {
                 Colour::BLACK
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// The reflectance of a thin film over base, or over nothing, seen along the ray.
+fn film_reflectance(
+    ray: &Ray,
+    hr: &HitRecord,
+    base: Option<&Material>,
+    thickness: &ScalarTexture,
+    refractive_index: &ScalarTexture,
+) -> Colour {
+    let (_, wo) = shading_frame(ray, hr);
+    let thickness = thickness(hr.u, hr.v, hr.p).max(0.0);
+    let film_index = refractive_index(hr.u, hr.v, hr.p);
+    let reflectance_at = |wavelength| {
+        let substrate = base.map_or((1.0, 0.0), |base| base.ior_at(hr, wavelength));
+        thin_film_reflectance(wo.z, film_index, substrate, thickness, wavelength)
+    };
+
+    match ray.wavelength {
+        Some(wavelength) => Colour::from(reflectance_at(wavelength)),
+        None => reflectance_to_rgb(reflectance_at),
+    }
+}
+
+// A frame around the normal on the side the ray comes from, and the direction back along
+// the ray in that frame.
+fn shading_frame(ray: &Ray, hr: &HitRecord) -> (Onb, Vec3) {
+    let wo = -Vec3::normalize(&ray.direction);
+    let normal = if Vec3::dot(&wo, &hr.normal) < 0.0 { -hr.normal } else { hr.normal };
+    let onb = Onb::from_w(normal);
+
+    (onb, onb.to_local(wo))
+}
+
+const MAX_COAT_BOUNCES: usize = 8;
+
+const PROFILE_MIN_COS: f64 = 0.05;
+
+// The transmittance of a coat of a material crossed in direction w, in a frame where the
+// coat lies in the xy plane.
+fn coat_transmittance(absorption: Colour, w: Vec3) -> Colour {
+    let cos = w.z.abs().max(1e-4);
+    absorption.map(|a| (-a / cos).exp())
+}
+
+// The transmittance along the ray up to the hit, if it travelled inside the material.
+fn beer_lambert(absorption: &Texture, ray: &Ray, hr: &HitRecord) -> Colour {
+    match hr.side {
+        Side::Outside => Colour::WHITE,
+        Side::Inside => {
+            let distance = hr.t * ray.direction.length();
+            absorption(hr.u, hr.v, hr.p).map(|a| (-a * distance).exp())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glass_absorbs_along_the_path_inside() {
+        seed_rng(5);
+        let absorption = Colour::new(0.1, 0.5, 2.0);
+        let glass = Material::Dielectric {
+            refractive_index: constant(1.5),
+            absorption: solid_colour(absorption),
+        };
+
+        // From inside the glass, with a direction of length 2, so 3 units to the surface.
+        let ray = Ray::new(Pos3::new(0.0, 0.0, 0.0), Vec3::normalize(&Vec3::new(0.0, 0.6, 1.6)) * 2.0, 0.0);
+        let hit = |side| HitRecord {
+            p: ray.at(1.5),
+            local: ray.at(1.5),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            t: 1.5,
+            u: 0.0,
+            v: 0.0,
+            side,
+            material: &glass,
+            dpdu: Vec3::new(1.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 1.0, 0.0),
+        };
+
+        let expected = absorption.map(|a| (-a * 3.0).exp());
+        for _ in 0..100 {
+            // Whether it refracts out or reflects back in, the light has come the same way.
+            let (_, weight, _) = glass.scatter(&ray, &hit(Side::Inside)).unwrap();
+            for (w, e) in [(weight.r, expected.r), (weight.g, expected.g), (weight.b, expected.b)] {
+                assert!((w - e).abs() < 1e-12, "{:?} against {:?}", weight, expected);
+            }
+        }
+
+        // Coming from outside, nothing was in the way.
+        let (_, weight, _) = glass.scatter(&ray, &hit(Side::Outside)).unwrap();
+        assert!(weight.all(|c| c == 1.0));
+    }
+}
@@ -0,0 +1,82 @@
+// Surface detail without geometry, by perturbing the normal a material sees. Both use
+// the tangents dpdu and dpdv of the hit, and keep the perturbed normal on the same side
+// of the surface as the original one.
+
+use super::*;
+
+// The step in u and v used to differentiate height maps.
+const BUMP_DELTA: f64 = 0.0005;
+
+// The normal of the surface displaced along its normal by the height at each point.
+pub fn bump_normal(hr: &HitRecord, height: &ScalarTexture) -> Vec3 {
+    let h = height(hr.u, hr.v, hr.p);
+    let dhdu = (height(hr.u + BUMP_DELTA, hr.v, hr.p + BUMP_DELTA * hr.dpdu) - h) / BUMP_DELTA;
+    let dhdv = (height(hr.u, hr.v + BUMP_DELTA, hr.p + BUMP_DELTA * hr.dpdv) - h) / BUMP_DELTA;
+
+    let dpdu = hr.dpdu + dhdu * hr.normal;
+    let dpdv = hr.dpdv + dhdv * hr.normal;
+    let normal = Vec3::cross(&dpdu, &dpdv);
+    if normal.length_squared() == 0.0 || normal.is_nan() {
+        return hr.normal;
+    }
+
+    same_side(Vec3::normalize(&normal), hr.normal)
+}
+
+// The normal from a tangent space normal map, with x along dpdu, y along dpdv and z along
+// the normal, each channel mapping [0, 1] to [-1, 1], as normal maps are usually stored.
+pub fn mapped_normal(hr: &HitRecord, normals: &Texture) -> Vec3 {
+    let n = hr.normal;
+    let tangent = hr.dpdu - Vec3::dot(&hr.dpdu, &n) * n;
+    if tangent.length_squared() < 1e-16 {
+        return n;
+    }
+    let tangent = Vec3::normalize(&tangent);
+    let mut bitangent = Vec3::cross(&n, &tangent);
+    if Vec3::dot(&bitangent, &hr.dpdv) < 0.0 {
+        bitangent = -bitangent;
+    }
+
+    let c = normals(hr.u, hr.v, hr.p);
+    let local = Vec3::new(2.0 * c.r - 1.0, 2.0 * c.g - 1.0, 2.0 * c.b - 1.0);
+    let normal = local.x * tangent + local.y * bitangent + local.z * n;
+    if normal.length_squared() == 0.0 {
+        return n;
+    }
+
+    same_side(Vec3::normalize(&normal), n)
+}
+
+fn same_side(normal: Vec3, reference: Vec3) -> Vec3 {
+    if Vec3::dot(&normal, &reference) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_maps_keep_the_normal() {
+        seed_rng(7);
+        let sphere = Sphere::new(Pos3::new(1.0, 2.0, 3.0), 1.5, Material::Lambertian {
+            albedo: solid_colour(Colour::from(0.5)),
+        });
+        let height = constant(0.3);
+        let normals = solid_colour(Colour::new(0.5, 0.5, 1.0));
+
+        for _ in 0..100 {
+            // All around the sphere, where its tangents turn with the point.
+            let origin = sphere.centre + 5.0 * random_unit_vec();
+            let ray = Ray::new(origin, sphere.centre - origin, 0.0);
+            let hr = sphere.hit(&ray, 0.001, INF).unwrap();
+
+            for normal in [bump_normal(&hr, &height), mapped_normal(&hr, &normals)] {
+                assert!((normal - hr.normal).length() < 1e-9, "{:?} against {:?} at {:?}", normal, hr.normal, hr.p);
+            }
+        }
+    }
+}
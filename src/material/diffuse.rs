@@ -0,0 +1,91 @@
+// Rough diffuse reflection, after "Generalization of Lambert's Reflectance Model", Oren
+// and Nayar 1994, in their qualitative form. Directions are in a local frame as for
+// microfacet.rs.
+
+use super::*;
+
+// Samples a direction with the cosine of its angle to the normal, returning it with its
+// weight f * cos / pdf relative to a Lambertian of the same albedo. sigma is the standard
+// deviation of the angle of the facets in radians, 0 being Lambertian.
+pub fn sample_oren_nayar(wo: Vec3, sigma: f64) -> (Vec3, f64) {
+    let wi = random_cosine_direction();
+    (wi, oren_nayar(wo, wi, sigma))
+}
+
+// The BRDF relative to a Lambertian one of the same albedo.
+pub fn oren_nayar(wo: Vec3, wi: Vec3, sigma: f64) -> f64 {
+    let sigma2 = sigma * sigma;
+    let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+    let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+    let sin_o = (1.0 - wo.z * wo.z).max(0.0).sqrt();
+    let sin_i = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+
+    // The cosine of the difference in azimuth.
+    let max_cos = if sin_o > 1e-4 && sin_i > 1e-4 {
+        ((wo.x * wi.x + wo.y * wi.y) / (sin_o * sin_i)).max(0.0)
+    } else {
+        0.0
+    };
+
+    // Sine of the larger and tangent of the smaller angle to the normal.
+    let (sin_alpha, tan_beta) = if wi.z < wo.z {
+        (sin_i, sin_o / wo.z.max(1e-4))
+    } else {
+        (sin_o, sin_i / wi.z.max(1e-4))
+    };
+
+    a + b * max_cos * sin_alpha * tan_beta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(cos: f64, phi: f64) -> Vec3 {
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        Vec3::new(sin * phi.cos(), sin * phi.sin(), cos)
+    }
+
+    const COSINES: [f64; 8] = [1.0, 0.8, 0.3, 1e-2, 1e-4, 1e-6, 1e-12, 0.0];
+
+    #[test]
+    fn smooth_is_lambertian() {
+        for &cos_o in &COSINES {
+            for &cos_i in &COSINES {
+                for &phi in &[0.0, 1.0, PI] {
+                    let (wo, wi) = (direction(cos_o, 0.0), direction(cos_i, phi));
+                    // Relative to a Lambertian, so albedo / pi itself.
+                    assert_eq!(oren_nayar(wo, wi, 0.0), 1.0);
+                }
+            }
+        }
+
+        // And as a material, albedo * cos / pi.
+        let albedo = Colour::new(0.2, 0.5, 0.8);
+        let sphere = Sphere::new(Pos3::new(0.0, 0.0, 0.0), 1.0, Material::OrenNayar {
+            albedo: solid_colour(albedo),
+            sigma: constant(0.0),
+        });
+        let ray = Ray::new(Pos3::new(0.3, 0.2, 3.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let hr = sphere.hit(&ray, 0.001, INF).unwrap();
+        let wi = Vec3::normalize(&Vec3::new(0.2, 0.9, 1.0));
+        let f = hr.material.eval(&ray, &hr, wi);
+        let expected = albedo * (Vec3::dot(&wi, &hr.normal) / PI);
+        assert!(f.zip_with(expected, |a, b| (a - b).abs()).all(|d| d < 1e-12), "{:?} vs {:?}", f, expected);
+    }
+
+    #[test]
+    fn finite_and_positive_at_grazing_angles() {
+        for &sigma in &[0.1, 0.5, 1.0, 3.0] {
+            for &cos_o in &COSINES {
+                for &cos_i in &COSINES {
+                    for &phi in &[0.0, 0.5, PI / 2.0, PI] {
+                        let f = oren_nayar(direction(cos_o, 0.0), direction(cos_i, phi), sigma);
+                        assert!(f.is_finite() && f >= 0.0, "{} for cosines {} and {} at sigma {}", f, cos_o, cos_i, sigma);
+                    }
+                }
+            }
+        }
+    }
+}
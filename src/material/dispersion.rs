@@ -0,0 +1,68 @@
+// Refractive indices that depend on the wavelength, which is what splits white light into
+// a rainbow in prisms and gems.
+
+// Coefficients for wavelengths in micrometres, as they are usually tabulated.
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / l^2
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    // n^2 = 1 + sum of b_i l^2 / (l^2 - c_i)
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl Dispersion {
+    // Borosilicate crown glass, the common optical glass.
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+        c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+    };
+
+    // Dense flint glass, with a lot more dispersion than crown glass.
+    pub const SF11: Dispersion = Dispersion::Sellmeier {
+        b: [1.737_596_95, 0.313_747_346, 1.898_781_01],
+        c: [0.013_188_707, 0.062_306_814_2, 155.236_29],
+    };
+
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [4.3356, 0.3306, 0.0],
+        c: [0.106 * 0.106, 0.175 * 0.175, 0.0],
+    };
+
+    // The refractive index at a wavelength in nanometres.
+    pub fn refractive_index(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_at_the_sodium_d_line() {
+        // As tabulated by the manufacturer, to the four decimals given.
+        assert!((Dispersion::BK7.refractive_index(587.6) - 1.5168).abs() < 1e-4);
+        assert!((Dispersion::SF11.refractive_index(587.6) - 1.7847).abs() < 1e-4);
+    }
+
+    #[test]
+    fn blue_bends_more_than_red() {
+        for dispersion in &[Dispersion::BK7, Dispersion::SF11, Dispersion::DIAMOND, Dispersion::Cauchy { a: 1.5, b: 0.004 }] {
+            assert!(dispersion.refractive_index(450.0) > dispersion.refractive_index(650.0), "{:?}", dispersion);
+        }
+    }
+}
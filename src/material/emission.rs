@@ -0,0 +1,52 @@
+// Shaping the light leaving an emitter.
+
+use crate::utility::*;
+
+// Emission concentrated around the normal of the emitter, angles in degrees from the
+// normal. Full within the inner angle, fading smoothly to nothing at the outer one.
+#[derive(Debug, Clone, Copy)]
+pub struct Spot {
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+}
+
+impl Spot {
+    // The fraction of the emission leaving at cos to the normal.
+    pub fn falloff(&self, cos: f64) -> f64 {
+        let cos_inner = deg_to_rad(self.inner_angle).cos();
+        let cos_outer = deg_to_rad(self.outer_angle).cos();
+        if cos >= cos_inner {
+            return 1.0;
+        }
+        if cos <= cos_outer || cos_inner <= cos_outer {
+            return 0.0;
+        }
+
+        // Smoothstep.
+        let t = (cos - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spot_falloff() {
+        let spot = Spot { inner_angle: 20.0, outer_angle: 40.0 };
+        let falloff = |deg: f64| spot.falloff(deg_to_rad(deg).cos());
+
+        for &deg in &[0.0, 10.0, 19.9, 20.0] {
+            assert_eq!(falloff(deg), 1.0, "{} degrees", deg);
+        }
+        for &deg in &[40.0, 40.1, 60.0, 90.0, 135.0] {
+            assert_eq!(falloff(deg), 0.0, "{} degrees", deg);
+        }
+
+        // Fading monotonically in between.
+        let fading: Vec<f64> = (1..40).map(|i| falloff(20.0 + i as f64 * 0.5)).collect();
+        assert!(fading.iter().all(|&f| f > 0.0 && f < 1.0));
+        assert!(fading.windows(2).all(|w| w[1] < w[0]));
+    }
+}
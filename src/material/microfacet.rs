@@ -0,0 +1,389 @@
+// The GGX (Trowbridge-Reitz) microfacet distribution with the height correlated Smith
+// shadowing-masking function, and Fresnel equations. Directions are in a local frame
+// where the macro surface normal is +z, as given by an Onb.
+
+use super::*;
+
+// Roughness is perceptually linear, alpha is what the distribution uses. Alpha is kept
+// above a minimum, since the distribution degenerates for a perfectly smooth surface.
+pub fn roughness_to_alpha(roughness: f64) -> f64 {
+    (roughness * roughness).max(1e-4)
+}
+
+pub fn is_smooth(roughness: f64) -> bool {
+    roughness < 1e-3
+}
+
+fn tan2_theta(w: Vec3) -> f64 {
+    let cos2 = w.z * w.z;
+    (1.0 - cos2).max(0.0) / cos2
+}
+
+fn smith_lambda(w: Vec3, alpha: f64) -> f64 {
+    if w.z.abs() < 1e-8 {
+        return INF;
+    }
+
+    (-1.0 + (1.0 + alpha * alpha * tan2_theta(w)).sqrt()) / 2.0
+}
+
+pub fn smith_g1(w: Vec3, alpha: f64) -> f64 {
+    1.0 / (1.0 + smith_lambda(w, alpha))
+}
+
+// Height correlated masking and shadowing of both directions.
+pub fn smith_g2(wo: Vec3, wi: Vec3, alpha: f64) -> f64 {
+    1.0 / (1.0 + smith_lambda(wo, alpha) + smith_lambda(wi, alpha))
+}
+
+// The distribution of microfacet normals.
+pub fn ggx_d(h: Vec3, alpha: f64) -> f64 {
+    if h.z <= 0.0 {
+        return 0.0;
+    }
+
+    let alpha2 = alpha * alpha;
+    let t = h.z * h.z * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * t * t)
+}
+
+// The reflection BRDF of a rough surface without its Fresnel term, with the half vector
+// to evaluate that at. None for directions below the surface.
+pub fn ggx_reflection(wo: Vec3, wi: Vec3, alpha: f64) -> Option<(Vec3, f64)> {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return None;
+    }
+
+    let h = Vec3::normalize(&(wo + wi));
+    Some((h, ggx_d(h, alpha) * smith_g2(wo, wi, alpha) / (4.0 * wo.z * wi.z)))
+}
+
+// The Fresnel-weighted reflection of a rough conductor times the cosine of wi, zero when
+// smooth since a mirror can't reflect light from any one direction.
+pub fn eval_conductor(wo: Vec3, wi: Vec3, ior: &ComplexIor, roughness: f64) -> Colour {
+    if is_smooth(roughness) {
+        return Colour::BLACK;
+    }
+
+    match ggx_reflection(wo, wi, roughness_to_alpha(roughness)) {
+        Some((h, f)) => fresnel_conductor(Vec3::dot(&wo, &h), ior) * f * wi.z,
+        None => Colour::BLACK,
+    }
+}
+
+// The weight G2 / G1 of a direction sampled from the visible normals, 1 for a smooth
+// surface. 0 when wo is so grazing that no normal is visible, rather than 0 / 0.
+pub fn masking(wo: Vec3, wi: Vec3, alpha: f64) -> f64 {
+    if alpha == 0.0 {
+        return 1.0;
+    }
+
+    let g1 = smith_g1(wo, alpha);
+    if g1 > 0.0 {
+        smith_g2(wo, wi, alpha) / g1
+    } else {
+        0.0
+    }
+}
+
+// Samples a microfacet normal from the distribution of normals visible from wo, which
+// has to be in the upper hemisphere. "Sampling the GGX Distribution of Visible Normals",
+// Heitz 2018.
+pub fn sample_ggx_vndf(wo: Vec3, alpha: f64) -> Vec3 {
+    let (u1, u2) = (random_zero_one(), random_zero_one());
+
+    // The view direction in the hemisphere configuration.
+    let vh = Vec3::normalize(&Vec3::new(alpha * wo.x, alpha * wo.y, wo.z));
+
+    let len2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if len2 > 0.0 {
+        Vec3::new(-vh.y, vh.x, 0.0) / len2.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = Vec3::cross(&vh, &t1);
+
+    // A point on the projected area of the hemisphere.
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+    // Back to the ellipsoid configuration.
+    Vec3::normalize(&Vec3::new(alpha * nh.x, alpha * nh.y, nh.z.max(1e-6)))
+}
+
+// The probability density of reflecting wo into wi off a normal sampled by
+// sample_ggx_vndf, 0 for directions below the surface.
+pub fn ggx_reflection_pdf(wo: Vec3, wi: Vec3, alpha: f64) -> f64 {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return 0.0;
+    }
+
+    let h = Vec3::normalize(&(wo + wi));
+    smith_g1(wo, alpha) * ggx_d(h, alpha) / (4.0 * wo.z)
+}
+
+// Fresnel reflectance of a dielectric interface for unpolarised light, with eta being
+// the index of refraction on the other side of the interface over that on the side of
+// the incident direction. 1 for total internal reflection.
+pub fn fresnel_dielectric(cos: f64, eta: f64) -> f64 {
+    let cos_i = clamp(0.0, 1.0, cos);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// Schlick's approximation with a coloured reflectance at normal incidence.
+pub fn fresnel_schlick(cos: f64, f0: Colour) -> Colour {
+    let t = (1.0 - clamp(0.0, 1.0, cos)).powi(5);
+    f0.map(|f| f + (1.0 - f) * t)
+}
+
+// A complex index of refraction per colour channel, describing a conductor.
+#[derive(Debug, Clone, Copy)]
+pub struct ComplexIor {
+    pub eta: Colour,
+    pub k: Colour,
+}
+
+impl ComplexIor {
+    pub const GOLD: ComplexIor = ComplexIor {
+        eta: Colour { r: 0.143, g: 0.374, b: 1.442 },
+        k: Colour { r: 3.983, g: 2.386, b: 1.603 },
+    };
+
+    pub const COPPER: ComplexIor = ComplexIor {
+        eta: Colour { r: 0.200, g: 0.924, b: 1.102 },
+        k: Colour { r: 3.912, g: 2.452, b: 2.142 },
+    };
+
+    pub const ALUMINIUM: ComplexIor = ComplexIor {
+        eta: Colour { r: 1.657, g: 0.880, b: 0.521 },
+        k: Colour { r: 9.224, g: 6.270, b: 4.837 },
+    };
+
+    pub const SILVER: ComplexIor = ComplexIor {
+        eta: Colour { r: 0.155, g: 0.117, b: 0.138 },
+        k: Colour { r: 4.828, g: 3.122, b: 2.147 },
+    };
+
+    pub fn new(eta: Colour, k: Colour) -> ComplexIor {
+        ComplexIor {
+            eta,
+            k,
+        }
+    }
+
+    // The real and imaginary part at a wavelength in nanometres, taking the blue, green
+    // and red channels to be at 450, 550 and 650 nm and interpolating between them.
+    pub fn at(&self, wavelength: f64) -> (f64, f64) {
+        let lerp = |c: Colour| {
+            let t = clamp(0.0, 2.0, (wavelength - 450.0) / 100.0);
+            if t < 1.0 {
+                (1.0 - t) * c.b + t * c.g
+            } else {
+                (2.0 - t) * c.g + (t - 1.0) * c.r
+            }
+        };
+
+        (lerp(self.eta), lerp(self.k))
+    }
+}
+
+// Fresnel reflectance of a conductor for unpolarised light, cos being the cosine of the
+// angle between the incident direction and the (micro)normal.
+pub fn fresnel_conductor(cos: f64, ior: &ComplexIor) -> Colour {
+    let cos = clamp(0.0, 1.0, cos);
+    let cos2 = cos * cos;
+    let sin2 = 1.0 - cos2;
+
+    let per_channel = |eta: f64, k: f64| {
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        clamp(0.0, 1.0, 0.5 * (rp + rs))
+    };
+
+    ior.eta.zip_with(ior.k, per_channel)
+}
+
+// Samples a reflection off a rough conductor, returning the direction and its weight
+// f * cos / pdf, which with visible normal sampling is F * G2 / G1. wo points away from
+// the surface and is in the local frame. None if the sampled direction ends up below
+// the surface.
+pub fn sample_conductor(wo: Vec3, ior: &ComplexIor, roughness: f64) -> Option<(Vec3, Colour)> {
+    if is_smooth(roughness) {
+        let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+        return Some((wi, fresnel_conductor(wo.z, ior)));
+    }
+
+    let alpha = roughness_to_alpha(roughness);
+    let h = sample_ggx_vndf(wo, alpha);
+    let wi = (-wo).reflect(h);
+    if wi.z <= 0.0 {
+        return None;
+    }
+
+    Some((wi, fresnel_conductor(Vec3::dot(&wo, &h), ior) * masking(wo, wi, alpha)))
+}
+
+// Samples a reflection or refraction through a rough dielectric interface, following
+// "Microfacet Models for Refraction through Rough Surfaces", Walter et al. 2007, with
+// visible normal sampling. eta is as for fresnel_dielectric. Reflection is chosen with
+// probability F, which cancels F out of the weight in both cases, leaving G2 / G1.
+pub fn sample_dielectric(wo: Vec3, eta: f64, roughness: f64) -> Option<(Vec3, f64)> {
+    let (h, alpha) = if is_smooth(roughness) {
+        (Vec3::new(0.0, 0.0, 1.0), 0.0)
+    } else {
+        let alpha = roughness_to_alpha(roughness);
+        (sample_ggx_vndf(wo, alpha), alpha)
+    };
+
+    let cos = Vec3::dot(&wo, &h);
+    let wi = if random_zero_one() < fresnel_dielectric(cos, eta) {
+        let wi = (-wo).reflect(h);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        wi
+    } else {
+        let wi = Vec3::normalize(&(-wo).refract(1.0 / eta, h)?);
+        if wi.z >= 0.0 {
+            return None;
+        }
+        wi
+    };
+
+    Some((wi, masking(wo, wi, alpha)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The direction at an angle with cosine cos to +z.
+    fn direction(cos: f64, phi: f64) -> Vec3 {
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        Vec3::new(sin * phi.cos(), sin * phi.sin(), cos)
+    }
+
+    #[test]
+    fn conductor_fresnel_at_normal_incidence() {
+        // ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2) per channel.
+        let ior = ComplexIor::GOLD;
+        let f0 = |n: f64, k: f64| ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+        let f = fresnel_conductor(1.0, &ior);
+        assert!((f.r - f0(ior.eta.r, ior.k.r)).abs() < 1e-9);
+        assert!((f.g - f0(ior.eta.g, ior.k.g)).abs() < 1e-9);
+        assert!((f.b - f0(ior.eta.b, ior.k.b)).abs() < 1e-9);
+        // Gold is yellow.
+        assert!(f.r > f.g && f.g > f.b);
+        // And every conductor reflects everything at grazing angles.
+        assert!((fresnel_conductor(0.0, &ior).g - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn conductor_samples_are_weighted_by_eval_over_pdf() {
+        seed_rng(1);
+        let ior = ComplexIor::COPPER;
+        for &roughness in &[0.1, 0.4, 0.9] {
+            let alpha = roughness_to_alpha(roughness);
+            for i in 0..200 {
+                let wo = direction(0.05 + 0.9 * (i as f64 / 200.0), i as f64);
+                let (wi, weight) = match sample_conductor(wo, &ior, roughness) {
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                let expected = eval_conductor(wo, wi, &ior, roughness) / ggx_reflection_pdf(wo, wi, alpha);
+                for (w, e) in [(weight.r, expected.r), (weight.g, expected.g), (weight.b, expected.b)] {
+                    assert!((w - e).abs() < 1e-6 * (1.0 + e), "{:?} against {:?} at roughness {}", weight, expected, roughness);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn perfect_conductors_reflect_nearly_everything_when_smooth() {
+        seed_rng(2);
+        // A huge extinction coefficient reflects everything at any angle.
+        let mirror = ComplexIor::new(Colour::from(1.0), Colour::from(1e4));
+        assert!(fresnel_conductor(0.5, &mirror).g > 0.9999);
+
+        let albedo = |roughness: f64, cos: f64| {
+            let n = 20_000;
+            let wo = direction(cos, 0.3);
+            (0..n).filter_map(|_| sample_conductor(wo, &mirror, roughness)).map(|(_, w)| w.g).sum::<f64>() / n as f64
+        };
+        for &cos in &[1.0, 0.5, 0.1] {
+            let (smooth, rough) = (albedo(0.05, cos), albedo(0.8, cos));
+            assert!(smooth <= 1.0 + 1e-9 && rough <= 1.0 + 1e-9, "albedo {} and {} at cos {}", smooth, rough, cos);
+            assert!(smooth > 0.97, "albedo {} at cos {}", smooth, cos);
+            // Rough surfaces lose the light that would bounce between microfacets.
+            assert!(rough < smooth);
+        }
+    }
+
+    #[test]
+    fn dielectrics_reflect_with_the_fresnel_probability() {
+        seed_rng(3);
+        let n = 20_000;
+        for &(cos, eta) in &[(0.9, 1.5), (0.3, 1.5), (0.7, 1.0 / 1.33)] {
+            let wo = direction(cos, 1.0);
+            let mut reflected = 0;
+            for _ in 0..n {
+                let (wi, weight) = sample_dielectric(wo, eta, 0.0).unwrap();
+                // Smooth, so all the light goes one way or the other.
+                assert_eq!(weight, 1.0);
+                if wi.z > 0.0 {
+                    reflected += 1;
+                    assert!((wi.z - cos).abs() < 1e-9);
+                }
+            }
+            let (r, f) = (reflected as f64 / n as f64, fresnel_dielectric(cos, eta));
+            assert!((r - f).abs() < 0.01, "reflected {} of the light, expected {}", r, f);
+        }
+
+        // Rough ones lose some to masking, but never gain any.
+        let wo = direction(0.5, 0.0);
+        let mean = (0..n).filter_map(|_| sample_dielectric(wo, 1.5, 0.5)).map(|(_, w)| w).sum::<f64>() / n as f64;
+        assert!(mean <= 1.0 && mean > 0.9, "mean weight {}", mean);
+    }
+
+    #[test]
+    fn total_internal_reflection_past_the_critical_angle() {
+        seed_rng(4);
+        // From glass into air the critical angle has sine 1 / 1.5.
+        let eta: f64 = 1.0 / 1.5;
+        let critical_cos = (1.0 - eta * eta).sqrt();
+        assert!(fresnel_dielectric(critical_cos + 0.01, eta) < 1.0);
+        assert_eq!(fresnel_dielectric(critical_cos - 0.01, eta), 1.0);
+
+        let wo = direction(critical_cos - 0.05, 2.0);
+        for _ in 0..1000 {
+            let (wi, weight) = sample_dielectric(wo, eta, 0.0).unwrap();
+            assert!(wi.z > 0.0);
+            assert_eq!(weight, 1.0);
+        }
+    }
+}
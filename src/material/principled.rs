@@ -0,0 +1,324 @@
+// A principled BSDF after "Physically Based Shading at Disney", Burley 2012.
+//
+// The lobes are layered from the top down: a clearcoat, then a base that is either metal
+// or dielectric, the dielectric one reflecting specularly and then refracting or
+// diffusing whatever it doesn't reflect. Each layer is picked with the probability that
+// light gets to it and reflects off it, rather than its contribution being added to the
+// others, so the material never reflects more light than arrives.
+
+use super::*;
+
+// Parameters are textures, use constant and solid_colour for uniform ones. All except
+// the IOR are in [0, 1].
+#[derive(Clone)]
+pub struct Principled {
+    pub base_colour: Texture,
+    pub metallic: ScalarTexture,
+    pub roughness: ScalarTexture,
+    // Specular reflectance of the dielectric base, 0.5 being that of its IOR.
+    pub specular: ScalarTexture,
+    // How much the specular reflection of the dielectric base takes on the base colour.
+    pub specular_tint: ScalarTexture,
+    // A soft white rim at grazing angles, e.g. for cloth.
+    pub sheen: ScalarTexture,
+    pub clearcoat: ScalarTexture,
+    // 0 for a satin coat, 1 for a glossy one.
+    pub clearcoat_gloss: ScalarTexture,
+    // How much of the light not reflected by the dielectric base refracts into it, rather
+    // than being diffused.
+    pub transmission: ScalarTexture,
+    pub ior: ScalarTexture,
+}
+
+impl Default for Principled {
+    // A white plastic.
+    fn default() -> Principled {
+        Principled {
+            base_colour: solid_colour(Colour::from(0.8)),
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_gloss: constant(1.0),
+            transmission: constant(0.0),
+            ior: constant(1.5),
+        }
+    }
+}
+
+const CLEARCOAT_IOR: f64 = 1.5;
+
+impl Principled {
+    // Samples a direction as for sample_dielectric, side being that of the ray, so that a
+    // transmissive material can be left again.
+    pub fn sample(&self, wo: Vec3, side: Side, u: f64, v: f64, p: Pos3) -> Option<(Vec3, Colour)> {
+        let param = |texture: &ScalarTexture| clamp(0.0, 1.0, texture(u, v, p));
+
+        let base_colour = (self.base_colour)(u, v, p).map(|c| clamp(0.0, 1.0, c));
+        let roughness = param(&self.roughness);
+        let transmission = param(&self.transmission);
+        let ior = (self.ior)(u, v, p).max(1.0);
+
+        // Inside, only the interface back out matters.
+        if side == Side::Inside && transmission > 0.0 {
+            return sample_dielectric(wo, 1.0 / ior, roughness).map(|(wi, weight)| (wi, Colour::from(weight)));
+        }
+
+        let clearcoat = param(&self.clearcoat);
+        if clearcoat > 0.0 {
+            let alpha = 0.1 + (0.001 - 0.1) * param(&self.clearcoat_gloss);
+            let h = sample_ggx_vndf(wo, alpha);
+            if random_zero_one() < clearcoat * fresnel_dielectric(Vec3::dot(&wo, &h), CLEARCOAT_IOR) {
+                return reflect(wo, h, alpha, Colour::WHITE);
+            }
+        }
+
+        let (h, alpha) = if is_smooth(roughness) {
+            (Vec3::new(0.0, 0.0, 1.0), 0.0)
+        } else {
+            let alpha = roughness_to_alpha(roughness);
+            (sample_ggx_vndf(wo, alpha), alpha)
+        };
+        let cos = Vec3::dot(&wo, &h);
+
+        if random_zero_one() < param(&self.metallic) {
+            return reflect(wo, h, alpha, fresnel_schlick(cos, base_colour));
+        }
+
+        let specular = clamp(0.0, 1.0, 2.0 * param(&self.specular) * fresnel_dielectric(cos, ior));
+        if random_zero_one() < specular {
+            let tint = Colour::col_lerp(Colour::WHITE, hue(base_colour), param(&self.specular_tint));
+            return reflect(wo, h, alpha, tint);
+        }
+
+        if random_zero_one() < transmission {
+            let wi = Vec3::normalize(&(-wo).refract(1.0 / ior, h)?);
+            if wi.z >= 0.0 {
+                return None;
+            }
+            return Some((wi, base_colour * masking(wo, wi, alpha)));
+        }
+
+        // Lambertian, its cosine cancelling out against that of the sampling.
+        let wi = random_cosine_direction();
+        let cos_d = Vec3::dot(&wi, &Vec3::normalize(&(wi + wo)));
+        let sheen = param(&self.sheen) * (1.0 - clamp(0.0, 1.0, cos_d)).powi(5);
+
+        Some((wi, Colour::col_lerp(base_colour, Colour::WHITE, sheen)))
+    }
+
+    // Whether sample can pick directions that eval doesn't account for.
+    pub fn has_specular(&self, u: f64, v: f64, p: Pos3) -> bool {
+        let param = |texture: &ScalarTexture| clamp(0.0, 1.0, texture(u, v, p));
+
+        param(&self.transmission) > 0.0 || is_smooth(param(&self.roughness))
+    }
+
+    // The BSDF times the cosine of wi, for the lobes that aren't specular. Each lobe is
+    // weighted by the probability that sample picks it, with the Fresnel terms evaluated
+    // at the half vector of wo and wi rather than at the sampled normal.
+    pub fn eval(&self, wo: Vec3, wi: Vec3, side: Side, u: f64, v: f64, p: Pos3) -> Colour {
+        let param = |texture: &ScalarTexture| clamp(0.0, 1.0, texture(u, v, p));
+
+        let transmission = param(&self.transmission);
+        if wi.z <= 0.0 || (side == Side::Inside && transmission > 0.0) {
+            return Colour::BLACK;
+        }
+
+        let base_colour = (self.base_colour)(u, v, p).map(|c| clamp(0.0, 1.0, c));
+        let roughness = param(&self.roughness);
+        let ior = (self.ior)(u, v, p).max(1.0);
+        let h = Vec3::normalize(&(wo + wi));
+        let cos = Vec3::dot(&wo, &h);
+        let mut col = Colour::BLACK;
+        let mut rest = 1.0;
+
+        let clearcoat = param(&self.clearcoat);
+        if clearcoat > 0.0 {
+            let alpha = 0.1 + (0.001 - 0.1) * param(&self.clearcoat_gloss);
+            let f = clearcoat * fresnel_dielectric(cos, CLEARCOAT_IOR);
+            if let Some((_, brdf)) = ggx_reflection(wo, wi, alpha) {
+                col += Colour::from(f * brdf * wi.z);
+            }
+            rest *= 1.0 - f;
+        }
+
+        let specular_brdf = if is_smooth(roughness) {
+            0.0
+        } else {
+            ggx_reflection(wo, wi, roughness_to_alpha(roughness)).map_or(0.0, |(_, brdf)| brdf * wi.z)
+        };
+
+        let metallic = param(&self.metallic);
+        col += rest * metallic * specular_brdf * fresnel_schlick(cos, base_colour);
+        rest *= 1.0 - metallic;
+
+        let specular = clamp(0.0, 1.0, 2.0 * param(&self.specular) * fresnel_dielectric(cos, ior));
+        let tint = Colour::col_lerp(Colour::WHITE, hue(base_colour), param(&self.specular_tint));
+        col += rest * specular * specular_brdf * tint;
+        rest *= (1.0 - specular) * (1.0 - transmission);
+
+        let sheen = param(&self.sheen) * (1.0 - clamp(0.0, 1.0, Vec3::dot(&wi, &h))).powi(5);
+        col + rest * wi.z / PI * Colour::col_lerp(base_colour, Colour::WHITE, sheen)
+    }
+    // The probability density of sample picking wi, for the lobes that aren't specular,
+    // with the same Fresnel terms as eval.
+    pub fn pdf(&self, wo: Vec3, wi: Vec3, side: Side, u: f64, v: f64, p: Pos3) -> f64 {
+        let param = |texture: &ScalarTexture| clamp(0.0, 1.0, texture(u, v, p));
+
+        let transmission = param(&self.transmission);
+        if wi.z <= 0.0 || (side == Side::Inside && transmission > 0.0) {
+            return 0.0;
+        }
+
+        let roughness = param(&self.roughness);
+        let ior = (self.ior)(u, v, p).max(1.0);
+        let cos = Vec3::dot(&wo, &Vec3::normalize(&(wo + wi)));
+        let mut pdf = 0.0;
+        let mut rest = 1.0;
+
+        let clearcoat = param(&self.clearcoat);
+        if clearcoat > 0.0 {
+            let alpha = 0.1 + (0.001 - 0.1) * param(&self.clearcoat_gloss);
+            let f = clearcoat * fresnel_dielectric(cos, CLEARCOAT_IOR);
+            pdf += f * ggx_reflection_pdf(wo, wi, alpha);
+            rest *= 1.0 - f;
+        }
+
+        let specular_pdf = if is_smooth(roughness) {
+            0.0
+        } else {
+            ggx_reflection_pdf(wo, wi, roughness_to_alpha(roughness))
+        };
+
+        let metallic = param(&self.metallic);
+        pdf += rest * metallic * specular_pdf;
+        rest *= 1.0 - metallic;
+
+        let specular = clamp(0.0, 1.0, 2.0 * param(&self.specular) * fresnel_dielectric(cos, ior));
+        pdf += rest * specular * specular_pdf;
+        rest *= (1.0 - specular) * (1.0 - transmission);
+
+        pdf + rest * wi.z / PI
+    }
+}
+
+fn reflect(wo: Vec3, h: Vec3, alpha: f64, weight: Colour) -> Option<(Vec3, Colour)> {
+    let wi = (-wo).reflect(h);
+    if wi.z <= 0.0 {
+        return None;
+    }
+
+    Some((wi, weight * masking(wo, wi, alpha)))
+}
+
+// The colour at full brightness, white for black.
+fn hue(col: Colour) -> Colour {
+    let max = col.r.max(col.g).max(col.b);
+    if max > 0.0 {
+        col / max
+    } else {
+        Colour::WHITE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Pos3 = Pos3 { x: 0.0, y: 0.0, z: 0.0 };
+
+    // The direction at an angle with cosine cos to +z.
+    fn direction(cos: f64, phi: f64) -> Vec3 {
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        Vec3::new(sin * phi.cos(), sin * phi.sin(), cos)
+    }
+
+    fn white(principled: Principled) -> Principled {
+        Principled {
+            base_colour: solid_colour(Colour::WHITE),
+            ..principled
+        }
+    }
+
+    #[test]
+    fn reflects_at_most_the_light_arriving() {
+        seed_rng(1);
+        let extremes = [
+            white(Principled { metallic: constant(1.0), roughness: constant(0.3), ..Principled::default() }),
+            white(Principled { specular: constant(1.0), roughness: constant(0.3), ..Principled::default() }),
+            white(Principled { transmission: constant(1.0), roughness: constant(0.3), ..Principled::default() }),
+            white(Principled { clearcoat: constant(1.0), sheen: constant(1.0), ..Principled::default() }),
+            white(Principled { transmission: constant(1.0), roughness: constant(0.0), ..Principled::default() }),
+        ];
+
+        for (i, principled) in extremes.iter().enumerate() {
+            for side in [Side::Outside, Side::Inside] {
+                let n = 20_000;
+                let sum: Colour = (0..n)
+                    .filter_map(|_| principled.sample(random_cosine_direction(), side, 0.0, 0.0, ORIGIN))
+                    .map(|(_, weight)| weight)
+                    .sum();
+                let mean = sum / n as f64;
+                assert!(mean.r.max(mean.g).max(mean.b) <= 1.0 + 1e-9, "mean weight {:?} of material {} on the {:?}", mean, i, side);
+            }
+        }
+    }
+
+    #[test]
+    fn no_nans_at_grazing_or_backfacing_directions() {
+        seed_rng(2);
+        let principled = Principled {
+            metallic: constant(0.5),
+            clearcoat: constant(0.5),
+            transmission: constant(0.5),
+            sheen: constant(1.0),
+            ..Principled::default()
+        };
+        let finite = |c: Colour| c.r.is_finite() && c.g.is_finite() && c.b.is_finite();
+
+        for &cos in &[1.0, 0.5, 1e-3, 1e-6, 1e-9, 1e-12, 0.0, -1e-9, -0.5] {
+            let wo = direction(cos, 0.7);
+            for side in [Side::Outside, Side::Inside] {
+                for _ in 0..1000 {
+                    if let Some((wi, weight)) = principled.sample(wo, side, 0.0, 0.0, ORIGIN) {
+                        assert!(!wi.is_nan() && finite(weight), "sampled {:?} with weight {:?} from {:?}", wi, weight, wo);
+                    }
+
+                    let wi = random_unit_vec();
+                    let f = principled.eval(wo, wi, side, 0.0, 0.0, ORIGIN);
+                    let pdf = principled.pdf(wo, wi, side, 0.0, 0.0, ORIGIN);
+                    assert!(finite(f) && pdf.is_finite() && pdf >= 0.0, "eval {:?} and pdf {} for {:?} from {:?}", f, pdf, wi, wo);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_weighted_by_eval_over_pdf() {
+        seed_rng(3);
+        // With a single lobe the weight of every sample is exactly f * cos / pdf. With more
+        // the choice between them depends on the sampled normal, so only its mean is.
+        let single_lobes = [
+            Principled { metallic: constant(1.0), roughness: constant(0.4), ..Principled::default() },
+            Principled { specular: constant(0.0), sheen: constant(0.8), ..Principled::default() },
+        ];
+
+        for principled in &single_lobes {
+            for i in 0..2000 {
+                let wo = direction(0.02 + 0.98 * (i as f64 / 2000.0), i as f64);
+                let (wi, weight) = match principled.sample(wo, Side::Outside, 0.0, 0.0, ORIGIN) {
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                let pdf = principled.pdf(wo, wi, Side::Outside, 0.0, 0.0, ORIGIN);
+                let expected = principled.eval(wo, wi, Side::Outside, 0.0, 0.0, ORIGIN) / pdf;
+                for (w, e) in [(weight.r, expected.r), (weight.g, expected.g), (weight.b, expected.b)] {
+                    assert!((w - e).abs() < 1e-6 * (1.0 + e), "{:?} against {:?} for {:?} from {:?}", weight, expected, wi, wo);
+                }
+            }
+        }
+    }
+}
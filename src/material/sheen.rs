@@ -0,0 +1,76 @@
+// A sheen lobe for cloth such as velvet, brightening grazing angles. The distribution
+// is the "Charlie" one from "Production Friendly Microfacet Sheen BRDF", Estevez and
+// Kulla 2017, with the visibility term of "Crafting a Next-Gen Material Pipeline for
+// The Order: 1886", Neubelt and Pettineo 2013. Directions are in a local frame as for
+// microfacet.rs.
+
+use super::*;
+
+use std::sync::OnceLock;
+
+fn sheen_alpha(roughness: f64) -> f64 {
+    let roughness = clamp(0.1, 1.0, roughness);
+    roughness * roughness
+}
+
+// The sheen BRDF, without its colour.
+pub fn sheen_brdf(wo: Vec3, wi: Vec3, roughness: f64) -> f64 {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return 0.0;
+    }
+
+    let alpha = sheen_alpha(roughness);
+    let h = Vec3::normalize(&(wo + wi));
+    let sin_h = (1.0 - h.z * h.z).max(0.0).sqrt();
+    let distribution = (2.0 + 1.0 / alpha) * sin_h.powf(1.0 / alpha) / (2.0 * PI);
+    let visibility = 1.0 / (4.0 * (wi.z + wo.z - wi.z * wo.z)).max(1e-4);
+
+    distribution * visibility
+}
+
+const ALBEDO_COS_STEPS: usize = 32;
+const ALBEDO_ROUGHNESS_STEPS: usize = 16;
+
+// The fraction of light arriving from wo that the sheen lobe reflects, tabulated over
+// the cosine of wo and the roughness, since the lobe doesn't integrate to anything
+// simple.
+pub fn sheen_albedo(cos: f64, roughness: f64) -> f64 {
+    static TABLE: OnceLock<Vec<f64>> = OnceLock::new();
+
+    let table = TABLE.get_or_init(|| {
+        let steps = 64;
+        let mut table = Vec::with_capacity(ALBEDO_COS_STEPS * ALBEDO_ROUGHNESS_STEPS);
+        for r in 0..ALBEDO_ROUGHNESS_STEPS {
+            let roughness = r as f64 / (ALBEDO_ROUGHNESS_STEPS - 1) as f64;
+            for c in 0..ALBEDO_COS_STEPS {
+                let cos_o = (c as f64 + 0.5) / ALBEDO_COS_STEPS as f64;
+                let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+
+                // Midpoint quadrature over the hemisphere.
+                let mut albedo = 0.0;
+                for i in 0..steps {
+                    let theta = (i as f64 + 0.5) / steps as f64 * PI / 2.0;
+                    for j in 0..steps {
+                        let phi = (j as f64 + 0.5) / steps as f64 * 2.0 * PI;
+                        let wi = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                        albedo += sheen_brdf(wo, wi, roughness) * theta.cos() * theta.sin();
+                    }
+                }
+                albedo *= (PI / 2.0 / steps as f64) * (2.0 * PI / steps as f64);
+
+                table.push(albedo.min(1.0));
+            }
+        }
+        table
+    });
+
+    // Bilinear interpolation.
+    let x = clamp(0.0, (ALBEDO_COS_STEPS - 1) as f64, cos * ALBEDO_COS_STEPS as f64 - 0.5);
+    let y = clamp(0.0, 1.0, roughness) * (ALBEDO_ROUGHNESS_STEPS - 1) as f64;
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(ALBEDO_COS_STEPS - 1), (y0 + 1).min(ALBEDO_ROUGHNESS_STEPS - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let at = |x: usize, y: usize| table[y * ALBEDO_COS_STEPS + x];
+
+    (1.0 - fy) * ((1.0 - fx) * at(x0, y0) + fx * at(x1, y0)) + fy * ((1.0 - fx) * at(x0, y1) + fx * at(x1, y1))
+}
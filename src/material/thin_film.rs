@@ -0,0 +1,165 @@
+// Thin film interference, e.g. soap bubbles and oil slicks. The film lies on top of the
+// material below it, whose index of refraction decides how much light the bottom of the
+// film reflects and with what phase, e.g. air below a bubble or metal below an oxide
+// layer. Light the film lets through is then left to that material, which reflects
+// some of it again if it has a surface of its own, e.g. a metal, so films are most
+// accurate over diffuse materials or where the film itself reflects most of the light.
+
+use super::*;
+
+// Reflectance of a film with the given index of refraction and thickness in nanometres
+// at a wavelength in nanometres, for unpolarised light arriving at cos to the normal.
+// The substrate is the real and imaginary part of the index of refraction below the
+// film. The light reflected off both sides of the film interferes, summed over all
+// internal reflections as by Airy.
+pub fn thin_film_reflectance(cos: f64, film_index: f64, substrate: (f64, f64), thickness: f64, wavelength: f64) -> f64 {
+    let cos_outside = clamp(1e-4, 1.0, cos);
+    let sin2_outside = 1.0 - cos_outside * cos_outside;
+    let sin2_inside = sin2_outside / (film_index * film_index);
+    let cos_inside = (1.0 - sin2_inside).max(0.0).sqrt();
+
+    // The phase difference between light reflected off the top and off the bottom.
+    let phase = 4.0 * PI * film_index * thickness * cos_inside / wavelength;
+    let delay = Complex::new(phase.cos(), phase.sin());
+
+    let airy = |r_top: Complex, r_bottom: Complex| {
+        let bottom = r_bottom * delay;
+        let denominator = (Complex::from(1.0) + r_top * bottom).norm_sqr();
+        if denominator < 1e-12 {
+            1.0
+        } else {
+            clamp(0.0, 1.0, (r_top + bottom).norm_sqr() / denominator)
+        }
+    };
+
+    // Fresnel amplitudes, going into the film at the top and out of it at the bottom,
+    // where q is the substrate index times the cosine in the substrate, complex for
+    // metals and past total internal reflection.
+    let (n, c1, c2) = (film_index, cos_outside, cos_inside);
+    let rs_top = Complex::from((c1 - n * c2) / (c1 + n * c2));
+    let rp_top = Complex::from((n * c1 - c2) / (n * c1 + c2));
+
+    let m = Complex::new(substrate.0, substrate.1);
+    let m2 = m * m;
+    let q = (m2 - Complex::from(sin2_outside)).sqrt();
+    let rs_bottom = (Complex::from(n * c2) - q) / (Complex::from(n * c2) + q);
+    let rp_bottom = (m2 * c2 - q * n) / (m2 * c2 + q * n);
+
+    (airy(rs_top, rs_bottom) + airy(rp_top, rp_bottom)) / 2.0
+}
+
+// Just enough complex arithmetic for the amplitudes.
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        Complex {
+            re,
+            im,
+        }
+    }
+
+    fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // The principal square root, with a non-negative real part.
+    fn sqrt(self) -> Complex {
+        let norm = self.norm_sqr().sqrt();
+        let re = (0.5 * (norm + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (norm - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Complex {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl std::ops::Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, x: f64) -> Complex {
+        Complex::new(self.re * x, self.im * x)
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let norm = other.norm_sqr();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / norm,
+            (self.im * other.re - self.re * other.im) / norm,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIR: (f64, f64) = (1.0, 0.0);
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn no_film_is_the_substrate() {
+        let gold = ComplexIor::new(Colour::from(0.4), Colour::from(2.5));
+        for &cos in &[1.0, 0.7, 0.3, 0.05] {
+            assert!(close(thin_film_reflectance(cos, 1.4, AIR, 0.0, 550.0), 0.0));
+            assert!(close(thin_film_reflectance(cos, 1.4, (1.5, 0.0), 0.0, 550.0), fresnel_dielectric(cos, 1.5)));
+            assert!(close(thin_film_reflectance(cos, 1.4, (0.4, 2.5), 0.0, 550.0), fresnel_conductor(cos, &gold).r));
+            // Nor is there a film to speak of if it is the same as what is below it.
+            assert!(close(thin_film_reflectance(cos, 1.5, (1.5, 0.0), 321.0, 550.0), fresnel_dielectric(cos, 1.5)));
+        }
+    }
+
+    // A quarter wave film of index sqrt(n) on glass of index n is the classic
+    // antireflection coating, while the same film in air reflects most strongly.
+    #[test]
+    fn quarter_wave_films() {
+        let (n, wavelength) = (1.9_f64, 550.0);
+        let film = n.sqrt();
+        let thickness = wavelength / (4.0 * film);
+
+        assert!(close(thin_film_reflectance(1.0, film, (n, 0.0), thickness, wavelength), 0.0));
+        let peak = thin_film_reflectance(1.0, film, AIR, thickness, wavelength);
+        for i in 0..20 {
+            assert!(thin_film_reflectance(1.0, film, AIR, thickness * (0.5 + i as f64 / 20.0), wavelength) <= peak + 1e-12);
+        }
+    }
+}
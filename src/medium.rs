@@ -0,0 +1,189 @@
+// Participating media that rays travel through between surfaces, e.g. the inside of a
+// subsurface material. Coefficients are per unit distance and per channel.
+
+use crate::colour::*;
+use crate::onb::*;
+use crate::vec3::*;
+use crate::utility::*;
+
+// How many times a path may scatter in a row inside a medium before Russian roulette
+// starts ending it, and the most it survives each further step with. Cutting walks off
+// instead would lose the light of long walks, darkening media that hardly absorb.
+pub const ROULETTE_WALK_STEPS: usize = 512;
+pub const MAX_WALK_SURVIVAL: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Medium {
+    pub absorption: Colour,
+    pub scattering: Colour,
+    pub phase: HenyeyGreenstein,
+}
+
+// The Henyey-Greenstein phase function, the distribution of directions light scatters
+// into. g in (-1, 1) is the average cosine of the angle it turns through, positive for
+// scattering forwards as in fog and clouds, negative for backwards and 0 for uniformly.
+#[derive(Debug, Clone, Copy)]
+pub struct HenyeyGreenstein {
+    pub g: f64,
+}
+
+// Where light travelling through a medium next interacts, with the weight of that
+// choice.
+pub enum FreeFlight {
+    Scattered {
+        distance: f64,
+        weight: Colour,
+    },
+    // Got to the end of the segment, e.g. a surface, without scattering.
+    Passed {
+        weight: Colour,
+    },
+}
+
+impl Medium {
+    pub fn extinction(&self) -> Colour {
+        self.absorption + self.scattering
+    }
+
+    pub fn transmittance(&self, distance: f64) -> Colour {
+        self.extinction().map(|s| if s == 0.0 { 1.0 } else { (-s * distance).exp() })
+    }
+
+    // Samples a distance from the extinction of a randomly chosen channel, weighted by
+    // the average probability over all channels, so that chromatic media work without
+    // tracing each channel separately.
+    pub fn sample_distance(&self, max_distance: f64) -> FreeFlight {
+        let extinction = self.extinction();
+        let channel = ((random_zero_one() * 3.0) as usize).min(2);
+        let distance = if extinction[channel] > 0.0 {
+            -(1.0 - random_zero_one()).ln() / extinction[channel]
+        } else {
+            INF
+        };
+
+        if distance < max_distance {
+            let transmittance = self.transmittance(distance);
+            let pdf = average(extinction * transmittance);
+
+            FreeFlight::Scattered {
+                distance,
+                weight: self.scattering * transmittance / pdf,
+            }
+        } else {
+            let transmittance = self.transmittance(max_distance);
+            let pdf = average(transmittance);
+
+            FreeFlight::Passed {
+                weight: if pdf > 0.0 { transmittance / pdf } else { Colour::BLACK },
+            }
+        }
+    }
+
+    // A new direction after scattering while travelling along direction.
+    pub fn sample_direction(&self, direction: Vec3) -> Vec3 {
+        self.phase.sample(direction)
+    }
+}
+
+impl HenyeyGreenstein {
+    pub const ISOTROPIC: HenyeyGreenstein = HenyeyGreenstein { g: 0.0 };
+
+    // The density per unit solid angle of light scattering into a direction at cos to the
+    // one it travelled in.
+    pub fn eval(&self, cos: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cos;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+
+    // A new unit direction for light travelling along direction, distributed as eval.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let g = self.g;
+        if g.abs() < 1e-3 {
+            return random_unit_vec();
+        }
+
+        // Inverting the cumulative distribution of the cosine.
+        let t = (1.0 - g * g) / (1.0 - g + 2.0 * g * random_zero_one());
+        let cos = clamp(-1.0, 1.0, (1.0 + g * g - t * t) / (2.0 * g));
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_zero_one();
+
+        Onb::from_w(direction).to_world(Vec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+    }
+}
+
+fn average(col: Colour) -> f64 {
+    (col.r + col.g + col.b) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GS: [f64; 6] = [-0.9, -0.3, 0.0, 0.0005, 0.5, 0.95];
+
+    #[test]
+    fn phase_function_integrates_to_one() {
+        for &g in &GS {
+            // Over the sphere, as an integral over the cosine times the 2 pi of the azimuth.
+            let steps = 200_000;
+            let integral: f64 = (0..steps)
+                .map(|i| {
+                    let cos = -1.0 + 2.0 * (i as f64 + 0.5) / steps as f64;
+                    2.0 * PI * HenyeyGreenstein { g }.eval(cos) * 2.0 / steps as f64
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "integral {} for g {}", integral, g);
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_phase_function() {
+        seed_rng(1);
+        let direction = Vec3::new(0.0, 2.0, -1.0);
+        let forward = Vec3::normalize(&direction);
+
+        for &g in &GS {
+            let phase = HenyeyGreenstein { g };
+            let n = 100_000;
+            let bins = 8;
+            let mut histogram = vec![0.0; bins];
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let wi = phase.sample(direction);
+                assert!((wi.length() - 1.0).abs() < 1e-9);
+                let cos = Vec3::dot(&forward, &wi);
+                sum += cos;
+                histogram[(((cos + 1.0) / 2.0 * bins as f64) as usize).min(bins - 1)] += 1.0 / n as f64;
+            }
+
+            // g is the mean cosine.
+            assert!((sum / n as f64 - g).abs() < 0.01, "mean cosine {} for g {}", sum / n as f64, g);
+
+            // The chance of each bin of cosines, from the cumulative distribution of eval.
+            let cdf = |cos: f64| {
+                let steps = 10_000;
+                (0..steps)
+                    .map(|i| {
+                        let c = -1.0 + (cos + 1.0) * (i as f64 + 0.5) / steps as f64;
+                        2.0 * PI * phase.eval(c) * (cos + 1.0) / steps as f64
+                    })
+                    .sum::<f64>()
+            };
+            for (i, &fraction) in histogram.iter().enumerate() {
+                let (lo, hi) = (-1.0 + 2.0 * i as f64 / bins as f64, -1.0 + 2.0 * (i + 1) as f64 / bins as f64);
+                let expected = cdf(hi) - cdf(lo);
+                assert!((fraction - expected).abs() < 0.005, "bin {} has {} of the samples, expected {} for g {}", i, fraction, expected, g);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_g_is_isotropic() {
+        let phase = HenyeyGreenstein::ISOTROPIC;
+        for &cos in &[-1.0, -0.4, 0.0, 0.3, 1.0] {
+            assert!((phase.eval(cos) - 1.0 / (4.0 * PI)).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::vec3::*;
+
+// An orthonormal basis, with w being the given direction (usually a surface normal).
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn from_w(w: Vec3) -> Onb {
+        let w = Vec3::normalize(&w);
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::normalize(&Vec3::cross(&w, &a));
+        let u = Vec3::cross(&v, &w);
+
+        Onb {
+            u,
+            v,
+            w,
+        }
+    }
+
+    pub fn to_world(&self, local: Vec3) -> Vec3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+
+    pub fn to_local(&self, world: Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(&world, &self.u),
+            Vec3::dot(&world, &self.v),
+            Vec3::dot(&world, &self.w),
+        )
+    }
+}
@@ -1,8 +1,9 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::convert::TryInto;
 
 use crate::consts::*;
 use crate::vec3::*;
+use crate::utility::*;
 
 pub struct Perlin {
     rand_vecs: Vec<Vec3>,
@@ -14,16 +15,14 @@ pub struct Perlin {
 impl Perlin {
     #[allow(dead_code)]
     pub fn new() -> Perlin {
-        let mut rng = thread_rng();
-
         let mut rand_vecs: Vec<Vec3> = Vec::with_capacity(PERLIN_POINT_COUNT);
         for _ in 0..PERLIN_POINT_COUNT {
             rand_vecs.push(Vec3::normalize(&random_vec_in_unit_sphere()));
         }
 
-        let perm_x = Perlin::gen_perm(&mut rng);
-        let perm_y = Perlin::gen_perm(&mut rng);
-        let perm_z = Perlin::gen_perm(&mut rng);
+        let (perm_x, perm_y, perm_z) = with_rng(|rng|
+            (Perlin::gen_perm(rng), Perlin::gen_perm(rng), Perlin::gen_perm(rng))
+        );
 
         Perlin {
             rand_vecs,
@@ -36,7 +35,7 @@ impl Perlin {
     fn gen_perm(rng: &mut impl Rng) -> Vec<u8> {
         let mut p: Vec<u8> = Vec::with_capacity(PERLIN_POINT_COUNT);
         for i in 0..PERLIN_POINT_COUNT {
-            p.push((i as usize).try_into().unwrap());
+            p.push(i.try_into().unwrap());
         }
         for i in (1..PERLIN_POINT_COUNT).rev() {
             p.swap(i, rng.gen_range(0, i));
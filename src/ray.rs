@@ -1,10 +1,16 @@
 use crate::vec3::*;
+use crate::medium::*;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Ray {
     pub origin: Pos3,
     pub direction: Vec3,
     pub time: f64,
+    // The wavelength in nanometres once the path has gone through something dispersive,
+    // see spectrum.rs. Until then the ray carries all wavelengths as RGB.
+    pub wavelength: Option<f64>,
+    // The medium the ray travels through, if it is inside e.g. a subsurface material.
+    pub medium: Option<Medium>,
 }
 
 impl Ray {
@@ -17,6 +23,18 @@ impl Ray {
             origin, 
             direction,
             time,
+            wavelength: None,
+            medium: None,
         }
     }
-}
\ No newline at end of file
+
+    // The next ray of the same path, at the same time and wavelength and in the same
+    // medium.
+    pub fn spawn(&self, origin: Pos3, direction: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction,
+            ..*self
+        }
+    }
+}
@@ -0,0 +1,628 @@
+use crate::colour::*;
+use crate::consts::*;
+use crate::film::*;
+use crate::filter::*;
+use crate::hit::*;
+use crate::light::*;
+use crate::medium::*;
+use crate::ray::*;
+use crate::scenes::*;
+use crate::utility::*;
+use crate::vec3::*;
+
+use rayon::prelude::*;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub samples_per_pass: usize,
+    pub max_bounces: usize,
+    pub tile_size: usize,
+    pub seed: u64,
+    pub time_budget: Option<Duration>,
+    pub crop: Option<Crop>,
+    // Whether a cropped render outputs the full frame, with everything outside the crop
+    // window left black, instead of just the crop window.
+    pub crop_full_frame: bool,
+    pub filter: Filter,
+    pub light_sampling: LightSampling,
+}
+
+// A window of the image to render, everything else is skipped. Since pixels keep their
+// full frame coordinates, the crop lines up exactly with a render of the full frame.
+#[derive(Debug, Clone, Copy)]
+pub enum Crop {
+    Pixels(PixelRect),
+    // The corners as fractions of the image size, with (0, 0) being the top left corner.
+    Normalised {
+        x0: f64,
+        y0: f64,
+        x1: f64,
+        y1: f64,
+    },
+}
+
+impl RenderSettings {
+    pub fn full_frame(&self) -> PixelRect {
+        PixelRect::new(0, 0, self.width, self.height)
+    }
+
+    // The pixels of the crop window, empty if it misses the image.
+    pub fn crop_rect(&self) -> PixelRect {
+        let full_frame = self.full_frame();
+        let crop = match self.crop {
+            None => return full_frame,
+            Some(Crop::Pixels(rect)) => rect,
+            Some(Crop::Normalised { x0, y0, x1, y1 }) => {
+                let to_pixels = |t: f64, size: usize| clamp(0.0, 1.0, t) * size as f64;
+                let (x0, x1) = (to_pixels(x0, self.width).floor() as usize, to_pixels(x1, self.width).ceil() as usize);
+                let (y0, y1) = (to_pixels(y0, self.height).floor() as usize, to_pixels(y1, self.height).ceil() as usize);
+                PixelRect::new(x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+            },
+        };
+
+        crop.intersect(&full_frame).unwrap_or_else(|| PixelRect::new(0, 0, 0, 0))
+    }
+
+    // The pixels that are traced, which includes those around the crop window whose
+    // samples reach into it through the filter.
+    pub fn trace_region(&self) -> PixelRect {
+        self.crop_rect().expand(self.filter.margin(), &self.full_frame())
+    }
+
+    // Turns a film of the crop window into the final output.
+    pub fn output_film(&self, film: Film) -> Film {
+        if self.crop_full_frame && film.rect != self.full_frame() {
+            let mut full_frame = Film::new(self.full_frame());
+            full_frame.merge(&film);
+            full_frame
+        } else {
+            film
+        }
+    }
+
+    // The work of a render, split into passes of samples_per_pass samples over every
+    // tile, so that stopping early still leaves every pixel with about as many samples.
+    pub fn passes(&self) -> impl Iterator<Item = Vec<Job>> {
+        let tiles = self.trace_region().tiles(self.tile_size);
+        let samples_per_pass = self.samples_per_pass.max(1);
+        let samples_per_pixel = self.samples_per_pixel;
+
+        (0..samples_per_pixel)
+            .step_by(samples_per_pass)
+            .map(move |start| {
+                let samples = start..(start + samples_per_pass).min(samples_per_pixel);
+                tiles.iter().map(|&rect| Job { rect, samples: samples.clone() }).collect()
+            })
+    }
+
+    pub fn num_jobs(&self) -> usize {
+        let passes = self.samples_per_pixel.div_ceil(self.samples_per_pass.max(1));
+        passes * self.trace_region().tiles(self.tile_size).len()
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            width: IMAGE_WIDTH,
+            height: IMAGE_HEIGHT,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            samples_per_pass: SAMPLES_PER_PASS,
+            max_bounces: MAX_BOUNCES,
+            tile_size: TILE_SIZE,
+            seed: SEED,
+            time_budget: None,
+            crop: None,
+            crop_full_frame: false,
+            filter: Filter::default(),
+            light_sampling: LightSampling::default(),
+        }
+    }
+}
+
+// A range of samples to take for every pixel in a rectangle.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Job {
+    pub rect: PixelRect,
+    pub samples: Range<usize>,
+}
+
+// Lets another thread stop a render, which then returns what it has rendered so far.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Decides when a render should stop taking new jobs, either because it was cancelled
+// or because its time budget ran out.
+#[derive(Debug, Clone)]
+pub struct StopCondition {
+    cancel: CancelToken,
+    deadline: Option<Instant>,
+}
+
+impl StopCondition {
+    pub fn new(settings: &RenderSettings, cancel: &CancelToken) -> StopCondition {
+        StopCondition {
+            cancel: cancel.clone(),
+            deadline: settings.time_budget.map(|budget| Instant::now() + budget),
+        }
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.cancel.is_cancelled() || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+// Renders the image one pass at a time, with the tiles of a pass in parallel. If
+// cancelled or out of time it stops between tiles, and every pixel is normalised by
+// the samples it actually got.
+pub fn render(scene: &Scene, settings: &RenderSettings, cancel: &CancelToken) -> Film {
+    let stop = StopCondition::new(settings, cancel);
+    let num_jobs = settings.num_jobs();
+    let jobs_done = AtomicUsize::new(0);
+    let film = Mutex::new(Film::new(settings.crop_rect()));
+
+    for pass in settings.passes() {
+        pass.par_iter().for_each(|job| {
+            if stop.should_stop() {
+                return;
+            }
+
+            let tile_film = render_tile(scene, settings, job.rect, job.samples.clone());
+            film.lock().unwrap().merge(&tile_film);
+            jobs_done.fetch_add(1, Ordering::Relaxed);
+        });
+
+        if let Some(job) = pass.first() {
+            eprintln!("Finished samples {}..{} ({}/{} tiles)", job.samples.start, job.samples.end, jobs_done.load(Ordering::Relaxed), num_jobs);
+        }
+
+        if stop.should_stop() && jobs_done.load(Ordering::Relaxed) < num_jobs {
+            eprintln!("Stopped early, {} of {} tiles rendered.", jobs_done.load(Ordering::Relaxed), num_jobs);
+            break;
+        }
+    }
+
+    settings.output_film(film.into_inner().unwrap())
+}
+
+// Renders a region of the image in parallel, one row at a time. The film also covers the
+// pixels around the region that its samples reach through the filter.
+pub fn render_region(scene: &Scene, settings: &RenderSettings, region: PixelRect, samples: Range<usize>) -> Film {
+    let film_rect = region.expand(settings.filter.margin(), &settings.full_frame());
+    let rows: Vec<PixelRect> = (region.y..region.y + region.height)
+        .map(|y| PixelRect::new(region.x, y, region.width, 1))
+        .collect();
+
+    rows.into_par_iter()
+        .map(|row| render_tile(scene, settings, row, samples.clone()))
+        .fold(|| Film::new(film_rect), |mut film, row_film| {
+            film.merge(&row_film);
+            film
+        })
+        .reduce(|| Film::new(film_rect), |mut a, b| {
+            a.merge(&b);
+            a
+        })
+}
+
+// Takes the given range of samples for every pixel in the tile, splatting them into a
+// film covering the tile and the pixels around it. Every sample reseeds the random
+// number generator from the pixel and sample index, so the result does not depend on
+// how the image is split into tiles and sample ranges, nor on which thread or process
+// rendered it.
+pub fn render_tile(scene: &Scene, settings: &RenderSettings, tile: PixelRect, samples: Range<usize>) -> Film {
+    let mut film = Film::new(tile.expand(settings.filter.margin(), &settings.full_frame()));
+
+    for y in tile.y..tile.y + tile.height {
+        for x in tile.x..tile.x + tile.width {
+            for s in samples.clone() {
+                seed_rng(hash_seed(settings.seed, &[x as u64, y as u64, s as u64]));
+
+                // Film rows grow downwards while v grows upwards.
+                let (jitter_x, jitter_y) = (random_zero_one(), random_zero_one());
+                let u = (x as f64 + jitter_x) / (settings.width as f64 - 1.0);
+                let v = ((settings.height - 1 - y) as f64 + jitter_y) / (settings.height as f64 - 1.0);
+                let ray = scene.camera.get_ray(u, v);
+                debug_assert!(!ray.direction.is_nan());
+                let col = ray_colour(scene, settings.light_sampling, &ray, settings.max_bounces, None);
+                debug_assert!(!col.is_nan());
+
+                film.splat(x as f64 + jitter_x, y as f64 + 1.0 - jitter_y, col, &settings.filter);
+            }
+        }
+    }
+
+    film
+}
+
+// Where the emitters among the lights of the scene were sampled for the path, and the
+// density of scattering in the direction of the ray there. Light from hitting them is
+// weighted against that from sampling them by multiple importance sampling.
+#[derive(Debug, Clone, Copy)]
+pub struct EmitterSampling {
+    pub p: Pos3,
+    pub pdf: f64,
+}
+
+// The weight of a sample taken with density pdf, out of two strategies, "Optimally
+// Combining Sampling Techniques for Monte Carlo Rendering", Veach and Guibas 1995.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    if pdf <= 0.0 {
+        return 0.0;
+    }
+
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    a / (a + b)
+}
+
+// emitters_sampled is where the emitters were sampled before the ray, None if they
+// weren't, in which case their light is only counted by hitting them.
+pub fn ray_colour(scene: &Scene, light_sampling: LightSampling, ray: &Ray, depth: usize, emitters_sampled: Option<EmitterSampling>) -> Colour {
+    if depth == 0 {
+        return Colour::BLACK;
+    }
+
+    // Inside a medium, walk through it from one scattering event to the next until getting
+    // to a surface.
+    let mut ray = *ray;
+    let mut hit = scene.hit(&ray, 0.001, INF);
+    let mut emitters_sampled = emitters_sampled;
+    let mut throughput = Colour::WHITE;
+    let mut steps = 0;
+    while let Some(medium) = ray.medium {
+        let length = ray.direction.length();
+        let max_distance = hit.map_or(INF, |(hr, _)| hr.t * length);
+
+        match medium.sample_distance(max_distance) {
+            FreeFlight::Scattered { distance, weight } => {
+                steps += 1;
+                throughput = throughput * weight;
+                if steps > ROULETTE_WALK_STEPS {
+                    let survival = throughput.r.max(throughput.g).max(throughput.b).min(MAX_WALK_SURVIVAL);
+                    if random_zero_one() >= survival {
+                        return Colour::BLACK;
+                    }
+                    throughput *= 1.0 / survival;
+                }
+                ray = ray.spawn(ray.at(distance / length), medium.sample_direction(ray.direction));
+                hit = scene.hit(&ray, 0.001, INF);
+                emitters_sampled = None;
+            },
+            FreeFlight::Passed { weight } => {
+                throughput = throughput * weight;
+                break;
+            },
+        }
+    }
+
+    let col = if let Some((hr, light)) = hit {
+        let mut emitted = hr.material.emit(&ray, &hr);
+        if let (Some(i), Some(sampled)) = (light, emitters_sampled) {
+            let wi = Vec3::normalize(&ray.direction);
+            let light_pdf = scene.light_sampler.pmf(light_sampling, sampled.p, i) * scene.lights[i].pdf(sampled.p, wi);
+            emitted *= power_heuristic(sampled.pdf, light_pdf);
+        }
+        let sample_emitters = !hr.material.has_specular(&hr);
+        emitted += direct_lighting(scene, light_sampling, &ray, &hr, sample_emitters);
+
+        if let Some((new_ray, attenuation, passed_through)) = hr.material.scatter(&ray, &hr) {
+            // Carrying on straight through, e.g. the hole of a cutout, continues the path
+            // from before, as shadow rays from there go through it as well.
+            let emitters_sampled = if passed_through {
+                emitters_sampled
+            } else if sample_emitters {
+                let pdf = hr.material.pdf(&ray, &hr, Vec3::normalize(&new_ray.direction));
+                Some(EmitterSampling { p: hr.p, pdf })
+            } else {
+                None
+            };
+            let res = ray_colour(scene, light_sampling, &new_ray, depth - 1, emitters_sampled);
+            let col = emitted + attenuation * res;
+            debug_assert!(!col.is_nan());
+            col
+        } else {
+            emitted
+        }
+    } else {
+        scene.background
+    };
+
+    throughput * col
+}
+
+// The light reflected along the ray straight from the lights of the scene. Delta lights
+// can't be hit by rays, so this is the only way their light gets into the image, whereas
+// emitters are only sampled if sample_emitters is set.
+fn direct_lighting(scene: &Scene, light_sampling: LightSampling, ray: &Ray, hr: &HitRecord, sample_emitters: bool) -> Colour {
+    let mut col = Colour::BLACK;
+    scene.light_sampler.for_each(&scene.lights, light_sampling, hr.p, |i, pmf| {
+        let light = &scene.lights[i];
+        if light.is_emitter() && !sample_emitters {
+            return;
+        }
+        let sample = match light.sample(hr.p) {
+            Some(sample) => sample,
+            None => return,
+        };
+        let f = hr.material.eval(ray, hr, sample.wi);
+        if f.all(|c| c == 0.0) {
+            return;
+        }
+
+        let shadow = ray.spawn(hr.p, sample.wi);
+        let transmittance = scene.transmittance(&shadow, 0.001, sample.distance - 0.001);
+        if transmittance > 0.0 {
+            let mis = if light.is_emitter() {
+                power_heuristic(pmf * light.pdf(hr.p, sample.wi), hr.material.pdf(ray, hr, sample.wi))
+            } else {
+                1.0
+            };
+            col += mis * transmittance * f * sample.weight * light.emitted(&shadow) / pmf;
+        }
+    });
+    col
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::*;
+    use crate::density::*;
+    use crate::material::*;
+    use crate::texture::*;
+
+    use std::thread;
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 24,
+            height: 16,
+            samples_per_pixel: 4,
+            samples_per_pass: 1,
+            max_bounces: 4,
+            tile_size: 8,
+            seed: 7,
+            ..RenderSettings::default()
+        }
+    }
+
+    #[test]
+    fn zero_time_budget_renders_nothing() {
+        let settings = RenderSettings {
+            time_budget: Some(Duration::ZERO),
+            ..settings()
+        };
+        let scene = build_scene("three_different_objects", settings.seed).unwrap();
+        let film = render(&scene, &settings, &CancelToken::new());
+
+        assert_eq!(film.rect, settings.full_frame());
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                assert_eq!(film.weight(x, y), 0.0);
+                assert!(film.pixel(x, y).all(|c| c == 0.0));
+            }
+        }
+    }
+
+    // Each pixel of a cancelled render is the average of the samples it got, the same
+    // as rendering just those samples.
+    #[test]
+    fn cancelled_render_is_normalised() {
+        let settings = RenderSettings {
+            samples_per_pixel: 64,
+            ..settings()
+        };
+        let scene = build_scene("three_different_objects", settings.seed).unwrap();
+        let cancel = CancelToken::new();
+
+        let film = thread::scope(|s| {
+            let canceller = cancel.clone();
+            s.spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                canceller.cancel();
+            });
+            render(&scene, &settings, &cancel)
+        });
+
+        // With the box filter every sample lands in its own pixel with weight 1.
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                let samples = film.weight(x, y);
+                assert_eq!(samples.fract(), 0.0);
+                assert!(samples <= settings.samples_per_pixel as f64);
+                let pixel = PixelRect::new(x, y, 1, 1);
+                let expected = render_tile(&scene, &settings, pixel, 0..samples as usize);
+                assert_same_pixels(&film, &expected, pixel);
+            }
+        }
+    }
+
+    // Pixels keep their full frame coordinates and samples, so a crop is exactly the same
+    // pixels of the full frame, including those at its edges that take samples from
+    // outside it through the filter.
+    #[test]
+    fn crop_matches_full_frame() {
+        let crops = [
+            Crop::Pixels(PixelRect::new(5, 3, 11, 7)),
+            Crop::Pixels(PixelRect::new(20, 12, 100, 100)),
+            Crop::Normalised { x0: 0.3, y0: 0.1, x1: 0.65, y1: 0.8 },
+        ];
+
+        for filter in [Filter::default(), Filter::from_name("mitchell", None).unwrap()] {
+            let settings = RenderSettings {
+                filter,
+                ..settings()
+            };
+            let scene = build_scene("three_different_objects", settings.seed).unwrap();
+            let full_frame = render(&scene, &settings, &CancelToken::new());
+
+            for crop in crops {
+                let settings = RenderSettings {
+                    crop: Some(crop),
+                    ..settings
+                };
+                let film = render(&scene, &settings, &CancelToken::new());
+
+                assert_eq!(film.rect, settings.crop_rect());
+                assert!(film.rect.area() > 0);
+                assert_same_pixels(&film, &full_frame, film.rect);
+            }
+        }
+    }
+
+    // A bulb inside a cutout with nothing but holes, seen through them either by sampling
+    // it or by hitting it, lights the floor as much as one without.
+    #[test]
+    fn emitters_behind_cutouts_count_once() {
+        let floor_colour = |cutout: bool| {
+            let mut objects: Objects = vec![
+                Box::new(XZRect::new(-10.0, 10.0, -10.0, 10.0, 0.0, Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                })),
+            ];
+            if cutout {
+                let material = Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                };
+                objects.push(Box::new(Sphere::new(Pos3::new(0.0, 2.0, 0.0), 1.0, Material::cutout(material, constant(0.0)))));
+            }
+            let bulb = Light::Sphere(Sphere::new(Pos3::new(0.0, 2.0, 0.0), 0.5, Material::DiffuseLight {
+                emit: solid_colour(Colour::WHITE),
+                intensity: 4.0,
+                two_sided: true,
+                spot: None,
+                profile: None,
+            }));
+            let camera = Camera::new(Pos3::new(0.0, 1.0, 3.0), Pos3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 40.0, 1.0, 0.0, 10.0, 0.0, 1.0);
+            let scene = Scene::new(camera, objects, Colour::BLACK, vec![bulb], 0.0, 1.0);
+
+            let ray = Ray::new(Pos3::new(0.3, 1.0, 3.0), Vec3::new(0.0, -1.0, -3.0), 0.0);
+            let n = 20_000;
+            let sum: Colour = (0..n)
+                .map(|i| {
+                    seed_rng(i);
+                    ray_colour(&scene, LightSampling::Bvh, &ray, 4, None)
+                })
+                .sum();
+            sum / n as f64
+        };
+
+        let (without, with) = (floor_colour(false), floor_colour(true));
+        assert!(without.r > 0.0);
+        assert!((with.r / without.r - 1.0).abs() < 0.05, "{:?} through the cutout against {:?} without", with, without);
+    }
+
+    #[test]
+    fn emitters_inside_bubbles_are_as_bright_sampled_or_not() {
+        // A bulb in a soap bubble over a floor, either sampled as a light or only found by
+        // hitting it, which have to agree on how much light gets through the film.
+        let floor_colour = |sampled: bool| {
+            let mut objects: Objects = vec![
+                Box::new(XZRect::new(-10.0, 10.0, -10.0, 10.0, 0.0, Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                })),
+                Box::new(Sphere::new(Pos3::new(0.0, 2.0, 0.0), 1.0, Material::ThinFilm {
+                    base: None,
+                    thickness: constant(300.0),
+                    refractive_index: constant(1.33),
+                })),
+            ];
+            let bulb = Sphere::new(Pos3::new(0.0, 2.0, 0.0), 0.5, Material::DiffuseLight {
+                emit: solid_colour(Colour::WHITE),
+                intensity: 4.0,
+                two_sided: true,
+                spot: None,
+                profile: None,
+            });
+            let lights = if sampled {
+                vec![Light::Sphere(bulb)]
+            } else {
+                objects.push(Box::new(bulb));
+                Vec::new()
+            };
+            let camera = Camera::new(Pos3::new(0.0, 1.0, 3.0), Pos3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 40.0, 1.0, 0.0, 10.0, 0.0, 1.0);
+            let scene = Scene::new(camera, objects, Colour::BLACK, lights, 0.0, 1.0);
+
+            let ray = Ray::new(Pos3::new(0.3, 1.0, 3.0), Vec3::new(0.0, -1.0, -3.0), 0.0);
+            let n = 40_000;
+            let sum: Colour = (0..n)
+                .map(|i| {
+                    seed_rng(i);
+                    ray_colour(&scene, LightSampling::Bvh, &ray, 4, None)
+                })
+                .sum();
+            sum / n as f64
+        };
+
+        let (hit, sampled) = (floor_colour(false), floor_colour(true));
+        assert!(hit.g > 0.0);
+        for (a, b) in [(hit.r, sampled.r), (hit.g, sampled.g), (hit.b, sampled.b)] {
+            assert!((b / a - 1.0).abs() < 0.05, "{:?} sampling the bulb against {:?} hitting it", sampled, hit);
+        }
+    }
+
+    // Looking straight through a ball of glowing, purely absorbing medium, what's seen is
+    // the emission along the ray less what's absorbed on the way out, (1 - e^(-density d))
+    // le / density for an emission per unit length le, whichever way the medium is tracked.
+    #[test]
+    fn homogeneous_emissive_media_glow_as_integrated() {
+        let (density, le) = (0.7, Colour::new(0.4, 0.2, 0.1));
+        let material = || Material::EmissiveMedium {
+            base: Box::new(Material::Isotropic {
+                albedo: solid_colour(Colour::BLACK),
+            }),
+            // Each collision adds the field, so emission per unit length is density times it.
+            emission: glow(le / density, uniform(1.0)),
+        };
+        let boundary = || Sphere::new(Pos3::new(0.0, 0.0, 0.0), 1.0, Material::Lambertian {
+            albedo: solid_colour(Colour::BLACK),
+        });
+        let media: Vec<Box<dyn Hit>> = vec![
+            Box::new(ConstantMedium::new(boundary(), density, material())),
+            Box::new(HeterogeneousMedium::new(boundary(), uniform(density), density, material())),
+        ];
+
+        let expected = (1.0 - (-density * 2.0).exp()) * le / density;
+        for medium in media {
+            let camera = Camera::new(Pos3::new(0.0, 0.0, 3.0), Pos3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 40.0, 1.0, 0.0, 10.0, 0.0, 1.0);
+            let scene = Scene::new(camera, vec![medium], Colour::BLACK, Vec::new(), 0.0, 1.0);
+
+            let ray = Ray::new(Pos3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+            let n = 40_000;
+            let sum: Colour = (0..n)
+                .map(|i| {
+                    seed_rng(i);
+                    ray_colour(&scene, LightSampling::Bvh, &ray, 4, None)
+                })
+                .sum();
+            let seen = sum / n as f64;
+
+            assert!(
+                seen.zip_with(expected, |a, b| (a / b - 1.0).abs()).all(|e| e < 0.02),
+                "{:?} against {:?}", seen, expected,
+            );
+        }
+    }
+}
+
@@ -1,5 +1,6 @@
 #![allow(clippy::redundant_clone)]
 #![allow(unused_variables)]
+#![allow(clippy::vec_init_then_push)]
 
 use crate::material::*;
 use crate::vec3::*;
@@ -11,11 +12,317 @@ use crate::perlin::*;
 use crate::hit::*;
 use crate::camera::*;
 use crate::consts::*;
+use crate::medium::*;
+use crate::spectrum::*;
+use crate::light::*;
+use crate::ray::*;
+use crate::density::*;
+use crate::aabb::*;
 
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
 pub type Objects = Vec<Box<dyn Hit>>;
 
+pub struct Scene {
+    pub camera: Camera,
+    pub objects: Objects,
+    pub background: Colour,
+    // Lights that are sampled directly, on top of the emissive objects that are only
+    // found by hitting them.
+    pub lights: Vec<Light>,
+    pub light_sampler: LightSampler,
+    // The emitters among the lights, for hitting them.
+    pub emitters: Objects,
+    // The index of the light of each emitter, by the address of its material, to tell
+    // which light a hit is on.
+    emitter_lights: HashMap<usize, usize>,
+}
+
+impl Scene {
+    pub fn new(camera: Camera, objects: Objects, background: Colour, lights: Vec<Light>, t_min: f64, t_max: f64) -> Scene {
+        let mut emitter_lights = HashMap::new();
+        let mut emitters: Objects = Vec::new();
+        for (i, light) in lights.iter().enumerate() {
+            if let Light::Sphere(sphere) = light {
+                let emitter = Box::new(sphere.clone());
+                emitter_lights.insert(&emitter.material as *const Material as usize, i);
+                emitters.push(emitter);
+            }
+        }
+        let emitters: Objects = if emitters.is_empty() {
+            emitters
+        } else {
+            vec![Box::new(Bvh::new(emitters, t_min, t_max))]
+        };
+
+        Scene {
+            camera,
+            objects,
+            background,
+            light_sampler: LightSampler::new(&lights),
+            lights,
+            emitters,
+            emitter_lights,
+        }
+    }
+
+    // The closest hit, and the index of the light if it is on one of the emitters among
+    // the lights.
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord<'_>, Option<usize>)> {
+        let hit = self.objects.hit(ray, t_min, t_max);
+        let t_max = hit.map_or(t_max, |hr| hr.t);
+
+        match self.emitters.hit(ray, t_min, t_max) {
+            Some(hr) => Some((hr, self.emitter_lights.get(&(hr.material as *const Material as usize)).copied())),
+            None => hit.map(|hr| (hr, None)),
+        }
+    }
+
+    // The fraction of light getting through between t_min and t_max along a shadow ray.
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let transmittance = self.objects.transmittance(ray, t_min, t_max);
+        if transmittance == 0.0 {
+            return 0.0;
+        }
+        transmittance * self.emitters.transmittance(ray, t_min, t_max)
+    }
+}
+
+pub const SCENE_NAMES: [&str; 30] = [
+    "final_scene_2",
+    "cornell_box_smoke",
+    "cornell_box",
+    "rectangle_light_test",
+    "texture_test",
+    "perlin_test",
+    "final_scene_1",
+    "test_bvh",
+    "three_different_objects",
+    "two_touching_objects",
+    "metals",
+    "frosted_glass",
+    "coloured_glass",
+    "dispersive_glass",
+    "principled",
+    "layered",
+    "rough_diffuse",
+    "mix",
+    "textured_materials",
+    "bump_mapping",
+    "subsurface",
+    "thin_film_and_sheen",
+    "emitters",
+    "delta_lights",
+    "ies_lights",
+    "many_lights",
+    "forward_scattering",
+    "clouds",
+    "voxel_smoke",
+    "fire",
+];
+
+// Builds one of the scenes below by name, together with a camera and background fitting it.
+// Scene construction is random, so the random number generator is seeded first to get
+// the same scene every time.
+pub fn build_scene(name: &str, seed: u64) -> Option<Scene> {
+    seed_rng(seed);
+
+    let sky = Colour::new(0.7, 0.8, 1.0);
+    let (t_min, t_max) = (0.0, 1.0);
+
+    let camera = |look_from: Pos3, look_at: Pos3, fov: f64, aperture: f64| {
+        Camera::new(
+            look_from,
+            look_at,
+            Vec3::new(0.0, 1.0, 0.0),
+            fov,
+            ASPECT_RATIO,
+            aperture,
+            10.0,
+            t_min,
+            t_max,
+        )
+    };
+    let cornell_camera = || camera(Pos3::new(278.0, 278.0, -800.0), Pos3::new(278.0, 278.0, 0.0), 40.0, 0.0);
+    let mut lights = vec![];
+
+    let (camera, objects, background) = match name {
+        "final_scene_2" => {
+            let (camera, objects) = final_scene_2(t_min, t_max);
+            (camera, objects, Colour::BLACK)
+        },
+        "cornell_box_smoke" => (cornell_camera(), cornell_box_smoke(t_min, t_max), Colour::BLACK),
+        "cornell_box" => (cornell_camera(), cornell_box(t_min, t_max), Colour::BLACK),
+        "rectangle_light_test" => (
+            camera(Pos3::new(26.0, 3.0, 6.0), Pos3::new(0.0, 2.0, 0.0), 20.0, 0.0),
+            rectangle_light_test(t_min, t_max),
+            Colour::BLACK,
+        ),
+        "texture_test" => (
+            camera(Pos3::new(26.0, 3.0, 6.0), Pos3::new(0.0, 2.0, 0.0), 20.0, 0.0),
+            texture_test(t_min, t_max),
+            Colour::BLACK,
+        ),
+        "perlin_test" => (
+            camera(Pos3::new(13.0, 2.0, 3.0), Pos3::new(0.0, 0.0, 0.0), 20.0, 0.0),
+            perlin_test(t_min, t_max),
+            sky,
+        ),
+        "final_scene_1" => (
+            camera(Pos3::new(13.0, 2.0, 3.0), Pos3::new(0.0, 0.0, 0.0), 20.0, 0.1),
+            final_scene_1(t_min, t_max),
+            sky,
+        ),
+        "test_bvh" => {
+            let (camera, objects) = test_bvh(t_min, t_max);
+            (camera, objects, sky)
+        },
+        "three_different_objects" => (
+            camera(Pos3::new(-2.0, 2.0, 1.0), Pos3::new(0.0, 0.0, -1.0), 40.0, 0.0),
+            three_different_objects(),
+            sky,
+        ),
+        "two_touching_objects" => (
+            camera(Pos3::new(0.0, 0.0, 0.0), Pos3::new(0.0, 0.0, -1.0), 90.0, 0.0),
+            two_touching_objects(),
+            sky,
+        ),
+        "metals" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            metals(t_min, t_max),
+            sky,
+        ),
+        "frosted_glass" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            frosted_glass(t_min, t_max),
+            sky,
+        ),
+        "coloured_glass" => (
+            camera(Pos3::new(2.0, 4.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 30.0, 0.0),
+            coloured_glass(t_min, t_max),
+            sky,
+        ),
+        "dispersive_glass" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            dispersive_glass(t_min, t_max),
+            sky,
+        ),
+        "principled" => (
+            camera(Pos3::new(0.0, 3.0, 16.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            principled(t_min, t_max),
+            sky,
+        ),
+        "layered" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            layered(t_min, t_max),
+            sky,
+        ),
+        "rough_diffuse" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            rough_diffuse(t_min, t_max),
+            Colour::BLACK,
+        ),
+        "mix" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            mix(t_min, t_max),
+            sky,
+        ),
+        "textured_materials" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            textured_materials(t_min, t_max),
+            sky,
+        ),
+        "bump_mapping" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            bump_mapping(t_min, t_max),
+            sky,
+        ),
+        "subsurface" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            subsurface(t_min, t_max),
+            sky,
+        ),
+        "thin_film_and_sheen" => (
+            camera(Pos3::new(0.0, 3.0, 16.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+            thin_film_and_sheen(t_min, t_max),
+            sky,
+        ),
+        "emitters" => (
+            camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.5, 0.0), 35.0, 0.0),
+            emitters(t_min, t_max),
+            Colour::BLACK,
+        ),
+        "delta_lights" => {
+            let (objects, scene_lights) = delta_lights(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+                objects,
+                Colour::BLACK,
+            )
+        },
+        "ies_lights" => {
+            let (objects, scene_lights) = ies_lights(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 2.0, 0.0), 35.0, 0.0),
+                objects,
+                Colour::BLACK,
+            )
+        },
+        "many_lights" => {
+            let (objects, scene_lights) = many_lights(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.0, 0.0), 35.0, 0.0),
+                objects,
+                Colour::BLACK,
+            )
+        },
+        "forward_scattering" => {
+            let (objects, scene_lights) = forward_scattering(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 3.0, 14.0), Pos3::new(0.0, 1.2, 0.0), 35.0, 0.0),
+                objects,
+                Colour::BLACK,
+            )
+        },
+        "clouds" => {
+            let (objects, scene_lights) = clouds(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 2.0, 12.0), Pos3::new(0.0, 2.0, 0.0), 40.0, 0.0),
+                objects,
+                Colour::new(0.5, 0.65, 0.9),
+            )
+        },
+        "voxel_smoke" => {
+            let (objects, scene_lights) = voxel_smoke(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 2.5, 11.0), Pos3::new(0.0, 1.6, 0.0), 40.0, 0.0),
+                objects,
+                Colour::new(0.5, 0.65, 0.9),
+            )
+        },
+        "fire" => {
+            let (objects, scene_lights) = fire(t_min, t_max);
+            lights = scene_lights;
+            (
+                camera(Pos3::new(0.0, 2.5, 11.0), Pos3::new(0.0, 1.6, 0.0), 40.0, 0.0),
+                objects,
+                Colour::new(0.01, 0.012, 0.03),
+            )
+        },
+        _ => return None,
+    };
+
+    Some(Scene::new(camera, objects, background, lights, t_min, t_max))
+}
+
 pub fn final_scene_2(t_min: f64, t_max: f64) -> (Camera, Objects) {
     // Floor boxes
     let ground_mat = Material::Lambertian {
@@ -62,6 +369,10 @@ pub fn final_scene_2(t_min: f64, t_max: f64) -> (Camera, Objects) {
     // Light
     let light_mat = Material::DiffuseLight {
         emit: solid_colour(Colour::from(7.0)),
+        intensity: 1.0,
+        two_sided: true,
+        spot: None,
+        profile: None,
     };
     objects.push(
         Box::new(
@@ -102,7 +413,8 @@ pub fn final_scene_2(t_min: f64, t_max: f64) -> (Camera, Objects) {
                 Pos3::new(260.0, 150.0, 45.0),
                 50.0,
                 Material::Dielectric {
-                    refractive_index: 1.5,
+                    refractive_index: constant(1.5),
+                    absorption: solid_colour(Colour::BLACK),
                 },
             )
         )
@@ -115,8 +427,8 @@ pub fn final_scene_2(t_min: f64, t_max: f64) -> (Camera, Objects) {
                 Pos3::new(0.0, 150.0, 145.0),
                 50.0,
                 Material::Metal {
-                    albedo: Colour::new(0.8, 0.8, 0.9),
-                    fuzziness: 10.0,
+                    albedo: solid_colour(Colour::new(0.8, 0.8, 0.9)),
+                    fuzziness: constant(10.0),
                 },
             )
         )
@@ -127,7 +439,8 @@ pub fn final_scene_2(t_min: f64, t_max: f64) -> (Camera, Objects) {
         Pos3::new(360.0, 150.0, 145.0),
         70.0,
         Material::Dielectric {
-            refractive_index: 1.5,
+            refractive_index: constant(1.5),
+            absorption: solid_colour(Colour::BLACK),
         },
     );
     objects.push(
@@ -152,7 +465,8 @@ pub fn final_scene_2(t_min: f64, t_max: f64) -> (Camera, Objects) {
         Pos3::from(0.0),
         5000.0,
         Material::Dielectric {
-            refractive_index: 1.5,
+            refractive_index: constant(1.5),
+            absorption: solid_colour(Colour::BLACK),
         },
     );
     objects.push(
@@ -264,7 +578,11 @@ pub fn cornell_box_smoke(t_min: f64, t_max: f64) -> Objects {
         albedo: solid_colour(Colour::new(0.12, 0.45, 0.15))
     };
     let light = Material::DiffuseLight {
-        emit: solid_colour(Colour::from(15.0))
+        emit: solid_colour(Colour::from(15.0)),
+        intensity: 1.0,
+        two_sided: true,
+        spot: None,
+        profile: None,
     };
 
     vec![
@@ -383,7 +701,11 @@ pub fn cornell_box(t_min: f64, t_max: f64) -> Objects {
         albedo: solid_colour(Colour::new(0.12, 0.45, 0.15))
     };
     let light = Material::DiffuseLight {
-        emit: solid_colour(Colour::from(15.0))
+        emit: solid_colour(Colour::from(15.0)),
+        intensity: 1.0,
+        two_sided: true,
+        spot: None,
+        profile: None,
     };
 
     vec![
@@ -513,7 +835,11 @@ pub fn rectangle_light_test(t_min: f64, t_max: f64) -> Objects {
                 y1: 3.0,
                 z: -2.0,
                 material: Material::DiffuseLight {
-                    emit: solid_colour(Colour::from(4.0))
+                    emit: solid_colour(Colour::from(4.0)),
+                    intensity: 1.0,
+                    two_sided: true,
+                    spot: None,
+                    profile: None,
                 },
             }
         )
@@ -538,6 +864,10 @@ pub fn texture_test(t_min: f64, t_max: f64) -> Objects {
                 centre: Pos3::new(0.0, 2.0, 0.0),
                 material: Material::DiffuseLight {
                     emit: texture.clone(),
+                    intensity: 1.0,
+                    two_sided: true,
+                    spot: None,
+                    profile: None,
                 },
                 radius: 2.0,
             }
@@ -611,8 +941,8 @@ pub fn final_scene_1(t_min: f64, t_max: f64) -> Objects {
                                 centre,
                                 radius: 0.2,
                                 material: Material::Metal {
-                                    albedo,
-                                    fuzziness,
+                                    albedo: solid_colour(albedo),
+                                    fuzziness: constant(fuzziness),
                                 },
                             }
                         )
@@ -624,7 +954,8 @@ pub fn final_scene_1(t_min: f64, t_max: f64) -> Objects {
                                 centre,
                                 radius: 0.2,
                                 material: Material::Dielectric {
-                                    refractive_index: 1.5,
+                                    refractive_index: constant(1.5),
+                                    absorption: solid_colour(Colour::BLACK),
                                 },
                             }
                         )
@@ -639,7 +970,8 @@ pub fn final_scene_1(t_min: f64, t_max: f64) -> Objects {
                 centre: Pos3::new(0.0, 1.0, 0.0),
                 radius: 1.0,
                 material: Material::Dielectric {
-                    refractive_index: 1.5,
+                    refractive_index: constant(1.5),
+                    absorption: solid_colour(Colour::BLACK),
                 },
             }
         )
@@ -661,8 +993,8 @@ pub fn final_scene_1(t_min: f64, t_max: f64) -> Objects {
                 centre: Pos3::new(4.0, 1.0, 0.0),
                 radius: 1.0,
                 material: Material::Metal {
-                    albedo: Colour::new(0.8, 0.7, 0.6),
-                    fuzziness: 0.2,
+                    albedo: solid_colour(Colour::new(0.8, 0.7, 0.6)),
+                    fuzziness: constant(0.2),
                 },
             }
         )
@@ -714,8 +1046,8 @@ pub fn test_bvh(t_min: f64, t_max: f64) -> (Camera, Objects) {
                                     centre,
                                     radius: 0.2,
                                     material: Material::Metal {
-                                        albedo,
-                                        fuzziness,
+                                        albedo: solid_colour(albedo),
+                                        fuzziness: constant(fuzziness),
                                     },
                                 }
                             )
@@ -727,7 +1059,8 @@ pub fn test_bvh(t_min: f64, t_max: f64) -> (Camera, Objects) {
                                     centre,
                                     radius: 0.2,
                                     material: Material::Dielectric {
-                                        refractive_index: 1.5,
+                                        refractive_index: constant(1.5),
+                                        absorption: solid_colour(Colour::BLACK),
                                     },
                                 }
                             )
@@ -744,7 +1077,8 @@ pub fn test_bvh(t_min: f64, t_max: f64) -> (Camera, Objects) {
                 centre: Pos3::new(0.0, 1.0, 0.0),
                 radius: 1.0,
                 material: Material::Dielectric {
-                    refractive_index: 1.5,
+                    refractive_index: constant(1.5),
+                    absorption: solid_colour(Colour::BLACK),
                 },
             }
         )
@@ -766,8 +1100,8 @@ pub fn test_bvh(t_min: f64, t_max: f64) -> (Camera, Objects) {
                 centre: Pos3::new(4.0, 1.0, 0.0),
                 radius: 1.0,
                 material: Material::Metal {
-                    albedo: Colour::new(0.7, 0.6, 0.5),
-                    fuzziness: 0.0,
+                    albedo: solid_colour(Colour::new(0.7, 0.6, 0.5)),
+                    fuzziness: constant(0.0),
                 },
             }
         )
@@ -825,8 +1159,8 @@ pub fn three_different_objects() -> Objects {
                 centre: Pos3::new(1.0, 0.0, -1.0), 
                 radius: 0.5,
                 material: Material::Metal {
-                    albedo: Colour::new(0.8, 0.6, 0.2),
-                    fuzziness: 0.0,
+                    albedo: solid_colour(Colour::new(0.8, 0.6, 0.2)),
+                    fuzziness: constant(0.0),
                 },
             }
         )
@@ -837,7 +1171,8 @@ pub fn three_different_objects() -> Objects {
                 centre: Pos3::new(-1.0, 0.0, -1.0), 
                 radius: 0.5,
                 material: Material::Dielectric {
-                    refractive_index: 1.5,
+                    refractive_index: constant(1.5),
+                    absorption: solid_colour(Colour::BLACK),
                 },
             }
         )
@@ -848,7 +1183,8 @@ pub fn three_different_objects() -> Objects {
                 centre: Pos3::new(-1.0, 0.0, -1.0), 
                 radius: -0.45,
                 material: Material::Dielectric {
-                    refractive_index: 1.5,
+                    refractive_index: constant(1.5),
+                    absorption: solid_colour(Colour::BLACK),
                 },
             }
         )
@@ -886,4 +1222,1364 @@ pub fn two_touching_objects() -> Objects {
     );
 
     objects
-}
\ No newline at end of file
+}
+
+pub fn metals(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.2)), solid_colour(Colour::from(0.8))),
+                },
+            }
+        )
+    );
+
+    let metals = [
+        (ComplexIor::GOLD, 0.05),
+        (ComplexIor::COPPER, 0.2),
+        (ComplexIor::ALUMINIUM, 0.4),
+        (ComplexIor::SILVER, 0.7),
+    ];
+    for (i, (ior, roughness)) in metals.iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-3.3 + 2.2 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material: Material::Conductor {
+                        eta: solid_colour(ior.eta),
+                        k: solid_colour(ior.k),
+                        roughness: constant(*roughness),
+                    },
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+pub fn frosted_glass(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+    objects.push(
+        Box::new(
+            XYRect::new(
+                -8.0,
+                8.0,
+                0.0,
+                6.0,
+                -3.0,
+                Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::new(0.8, 0.1, 0.1)), solid_colour(Colour::new(0.9, 0.9, 0.2))),
+                },
+            )
+        )
+    );
+
+    for (i, roughness) in [0.0, 0.15, 0.4].iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-2.4 + 2.4 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material: Material::RoughDielectric {
+                        refractive_index: constant(1.5),
+                        roughness: constant(*roughness),
+                        absorption: solid_colour(Colour::BLACK),
+                    },
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// Slabs of the same tinted glass, getting darker with thickness.
+pub fn coloured_glass(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.3)), solid_colour(Colour::from(0.9))),
+                },
+            }
+        )
+    );
+
+    let glass = Material::RoughDielectric {
+        refractive_index: constant(1.5),
+        roughness: constant(0.0),
+        absorption: solid_colour(Colour::new(0.8, 0.15, 0.5)),
+    };
+    for (i, thickness) in [0.2, 0.8, 2.4].iter().enumerate() {
+        let x = -3.0 + 2.2 * i as f64;
+        objects.push(
+            Box::new(
+                Cuboid::new(
+                    Pos3::new(x, 0.0, -thickness / 2.0),
+                    Pos3::new(x + 1.6, 2.5, thickness / 2.0),
+                    glass.clone(),
+                )
+            )
+        );
+    }
+
+    objects
+}
+
+// Crown glass, flint glass and diamond in front of a black and white backdrop, the
+// colour fringes at its edges getting stronger with the dispersion of the material.
+pub fn dispersive_glass(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+    objects.push(
+        Box::new(
+            XYRect::new(
+                -8.0,
+                8.0,
+                0.0,
+                6.0,
+                -3.0,
+                Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.02)), solid_colour(Colour::from(0.95))),
+                },
+            )
+        )
+    );
+
+    for (i, dispersion) in [Dispersion::BK7, Dispersion::SF11, Dispersion::DIAMOND].iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-2.4 + 2.4 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material: Material::DispersiveDielectric {
+                        dispersion: *dispersion,
+                        roughness: constant(0.0),
+                        absorption: solid_colour(Colour::BLACK),
+                    },
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// The principled material as plastic, brushed gold, car paint, frosted glass and velvet.
+pub fn principled(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.3)), solid_colour(Colour::from(0.7))),
+                },
+            }
+        )
+    );
+
+    let materials = vec![
+        Principled {
+            base_colour: solid_colour(Colour::new(0.1, 0.3, 0.8)),
+            roughness: constant(0.3),
+            ..Principled::default()
+        },
+        Principled {
+            base_colour: solid_colour(Colour::new(1.0, 0.78, 0.34)),
+            metallic: constant(1.0),
+            roughness: constant(0.35),
+            ..Principled::default()
+        },
+        Principled {
+            base_colour: solid_colour(Colour::new(0.6, 0.02, 0.02)),
+            metallic: constant(0.5),
+            roughness: constant(0.4),
+            clearcoat: constant(1.0),
+            ..Principled::default()
+        },
+        Principled {
+            base_colour: solid_colour(Colour::new(0.9, 1.0, 0.95)),
+            roughness: constant(0.1),
+            transmission: constant(1.0),
+            ..Principled::default()
+        },
+        Principled {
+            base_colour: checkered(solid_colour(Colour::new(0.4, 0.05, 0.3)), solid_colour(Colour::new(0.2, 0.02, 0.15))),
+            roughness: constant(1.0),
+            specular: constant(0.0),
+            sheen: constant(1.0),
+            ..Principled::default()
+        },
+    ];
+
+    for (i, material) in materials.into_iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-4.4 + 2.2 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material: Material::Principled(Box::new(material)),
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// Car paint, lacquered wood, coated brushed metal and a satin coat, each a coat over
+// another material.
+pub fn layered(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.3)), solid_colour(Colour::from(0.7))),
+                },
+            }
+        )
+    );
+
+    let materials = vec![
+        Material::Layered {
+            base: Box::new(Material::Lambertian {
+                albedo: solid_colour(Colour::new(0.7, 0.05, 0.05)),
+            }),
+            refractive_index: constant(1.5),
+            roughness: constant(0.0),
+            absorption: solid_colour(Colour::BLACK),
+        },
+        Material::Layered {
+            base: Box::new(Material::Lambertian {
+                albedo: noise(Perlin::new(), 3.0),
+            }),
+            refractive_index: constant(1.5),
+            roughness: constant(0.05),
+            absorption: solid_colour(Colour::new(0.2, 0.6, 1.5)),
+        },
+        Material::Layered {
+            base: Box::new(Material::Metal {
+                albedo: solid_colour(Colour::new(0.8, 0.8, 0.85)),
+                fuzziness: constant(0.4),
+            }),
+            refractive_index: constant(1.5),
+            roughness: constant(0.0),
+            absorption: solid_colour(Colour::new(0.0, 0.3, 0.6)),
+        },
+        Material::Layered {
+            base: Box::new(Material::Lambertian {
+                albedo: solid_colour(Colour::new(0.1, 0.3, 0.6)),
+            }),
+            refractive_index: constant(1.5),
+            roughness: constant(0.3),
+            absorption: solid_colour(Colour::BLACK),
+        },
+    ];
+
+    for (i, material) in materials.into_iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-3.3 + 2.2 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material,
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// Lambertian and increasingly rough Oren-Nayar spheres, lit from behind the camera so
+// that the rough ones look flat, like the full moon.
+pub fn rough_diffuse(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::OrenNayar {
+                    albedo: solid_colour(Colour::from(0.5)),
+                    sigma: constant(0.5),
+                },
+            }
+        )
+    );
+    objects.push(
+        Box::new(
+            XYRect::new(
+                -12.0,
+                12.0,
+                -2.0,
+                14.0,
+                20.0,
+                Material::DiffuseLight {
+                    emit: solid_colour(Colour::from(1.5)),
+                    intensity: 1.0,
+                    two_sided: true,
+                    spot: None,
+                    profile: None,
+                },
+            )
+        )
+    );
+
+    let albedo = solid_colour(Colour::new(0.8, 0.6, 0.45));
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(-2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::Lambertian {
+                    albedo: albedo.clone(),
+                },
+            }
+        )
+    );
+    for (i, sigma) in [0.5, 1.2].iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(2.4 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material: Material::OrenNayar {
+                        albedo: albedo.clone(),
+                        sigma: constant(*sigma),
+                    },
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// Rust patches on metal, a smooth blend of paint and gold, and a sphere with checkered
+// holes cut out of it.
+pub fn mix(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+
+    let perlin = Perlin::new();
+    let rust_mask: ScalarTexture = Arc::new(
+        move |_, _, p| if perlin.turb(p * 2.0, 7) > 0.4 { 1.0 } else { 0.0 }
+    );
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(-2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::Mix {
+                    first: Box::new(Material::Conductor {
+                        eta: solid_colour(ComplexIor::ALUMINIUM.eta),
+                        k: solid_colour(ComplexIor::ALUMINIUM.k),
+                        roughness: constant(0.2),
+                    }),
+                    second: Box::new(Material::OrenNayar {
+                        albedo: solid_colour(Colour::new(0.45, 0.2, 0.08)),
+                        sigma: constant(1.0),
+                    }),
+                    mask: rust_mask,
+                },
+            }
+        )
+    );
+
+    let gradient: ScalarTexture = Arc::new(
+        |_, _, p| clamp(0.0, 1.0, p.y / 2.0)
+    );
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::Mix {
+                    first: Box::new(Material::Lambertian {
+                        albedo: solid_colour(Colour::new(0.1, 0.3, 0.7)),
+                    }),
+                    second: Box::new(Material::Conductor {
+                        eta: solid_colour(ComplexIor::GOLD.eta),
+                        k: solid_colour(ComplexIor::GOLD.k),
+                        roughness: constant(0.1),
+                    }),
+                    mask: gradient,
+                },
+            }
+        )
+    );
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::cutout(
+                    Material::Lambertian {
+                        albedo: solid_colour(Colour::new(0.8, 0.3, 0.1)),
+                    },
+                    grey(checkered(solid_colour(Colour::BLACK), solid_colour(Colour::WHITE))),
+                ),
+            }
+        )
+    );
+
+    objects
+}
+
+// Parameters driven by textures: a metal with the earth as albedo and its oceans
+// polished, glass with patches of frost, and a dielectric with a varying IOR.
+pub fn textured_materials(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.3)), solid_colour(Colour::from(0.7))),
+                },
+            }
+        )
+    );
+
+    let earth = image(Path::new("assets/earthmap.jpg"));
+    let oceans = earth.clone();
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(-2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::Metal {
+                    albedo: earth,
+                    fuzziness: Arc::new(
+                        move |u, v, p| {
+                            let col = oceans(u, v, p);
+                            if col.b > col.r { 0.0 } else { 0.5 }
+                        }
+                    ),
+                },
+            }
+        )
+    );
+
+    let perlin = Perlin::new();
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::RoughDielectric {
+                    refractive_index: constant(1.5),
+                    roughness: Arc::new(
+                        move |_, _, p| if perlin.turb(p * 2.0, 7) > 0.4 { 0.4 } else { 0.0 }
+                    ),
+                    absorption: solid_colour(Colour::BLACK),
+                },
+            }
+        )
+    );
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::Dielectric {
+                    refractive_index: Arc::new(
+                        |_, _, p| 1.3 + 0.4 * clamp(0.0, 1.0, p.y / 2.0)
+                    ),
+                    absorption: solid_colour(Colour::new(0.0, 0.1, 0.2)),
+                },
+            }
+        )
+    );
+
+    objects
+}
+
+// Stucco from a noise height map, hammered metal from a procedural normal map and a
+// ribbed floor, all on smooth geometry.
+pub fn bump_mapping(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            XZRect::new(
+                -20.0,
+                20.0,
+                -20.0,
+                20.0,
+                0.0,
+                Material::BumpMap {
+                    base: Box::new(Material::Lambertian {
+                        albedo: solid_colour(Colour::from(0.5)),
+                    }),
+                    height: Arc::new(
+                        |u, _, _| 0.05 * (160.0 * PI * u).sin()
+                    ),
+                },
+            )
+        )
+    );
+
+    let perlin = Perlin::new();
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(-2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::BumpMap {
+                    base: Box::new(Material::Lambertian {
+                        albedo: solid_colour(Colour::new(0.8, 0.7, 0.5)),
+                    }),
+                    height: Arc::new(
+                        move |_, _, p| 0.05 * perlin.turb(p * 4.0, 7)
+                    ),
+                },
+            }
+        )
+    );
+
+    // Round dents on a grid in uv.
+    let dents: Texture = Arc::new(
+        |u, v, _| {
+            let x = (u * 24.0).fract() - 0.5;
+            let y = (v * 12.0).fract() - 0.5;
+            let normal = if x * x + y * y < 0.16 {
+                Vec3::normalize(&Vec3::new(-x, -y, 0.6))
+            } else {
+                Vec3::new(0.0, 0.0, 1.0)
+            };
+
+            Colour::new(normal.x + 1.0, normal.y + 1.0, normal.z + 1.0) / 2.0
+        }
+    );
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::NormalMap {
+                    base: Box::new(Material::Conductor {
+                        eta: solid_colour(ComplexIor::COPPER.eta),
+                        k: solid_colour(ComplexIor::COPPER.k),
+                        roughness: constant(0.15),
+                    }),
+                    normals: dents,
+                },
+            }
+        )
+    );
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(2.4, 1.0, 0.0),
+                radius: 1.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::new(0.8, 0.7, 0.5)),
+                },
+            }
+        )
+    );
+
+    objects
+}
+
+// Wax, jade and skin, lit through from behind by a large light.
+pub fn subsurface(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+    objects.push(
+        Box::new(
+            XYRect::new(
+                -6.0,
+                6.0,
+                0.0,
+                5.0,
+                -4.0,
+                Material::DiffuseLight {
+                    emit: solid_colour(Colour::from(3.0)),
+                    intensity: 1.0,
+                    two_sided: true,
+                    spot: None,
+                    profile: None,
+                },
+            )
+        )
+    );
+
+    let media = [
+        Medium {
+            absorption: Colour::new(0.01, 0.03, 0.1),
+            scattering: Colour::from(4.0),
+            phase: HenyeyGreenstein::ISOTROPIC,
+        },
+        Medium {
+            absorption: Colour::new(0.4, 0.05, 0.3),
+            scattering: Colour::from(2.0),
+            phase: HenyeyGreenstein::ISOTROPIC,
+        },
+        Medium {
+            absorption: Colour::new(0.03, 0.2, 0.3),
+            scattering: Colour::new(1.5, 4.0, 6.0),
+            phase: HenyeyGreenstein::ISOTROPIC,
+        },
+    ];
+    for (i, medium) in media.iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-2.4 + 2.4 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material: Material::Subsurface {
+                        refractive_index: constant(1.4),
+                        roughness: constant(0.2),
+                        medium: *medium,
+                    },
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// A soap bubble, an oil slick, a film getting thicker upwards, velvet and a bare sheen.
+pub fn thin_film_and_sheen(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: checkered(solid_colour(Colour::from(0.3)), solid_colour(Colour::from(0.7))),
+                },
+            }
+        )
+    );
+
+    let perlin = Perlin::new();
+    let swirls: ScalarTexture = Arc::new(
+        move |_, _, p| 250.0 + 500.0 * perlin.turb(p * 0.7, 7)
+    );
+
+    let materials = vec![
+        Material::ThinFilm {
+            base: None,
+            thickness: swirls.clone(),
+            refractive_index: constant(1.33),
+        },
+        Material::ThinFilm {
+            base: Some(Box::new(Material::Lambertian {
+                albedo: solid_colour(Colour::from(0.03)),
+            })),
+            thickness: swirls,
+            refractive_index: constant(1.45),
+        },
+        Material::ThinFilm {
+            base: Some(Box::new(Material::Lambertian {
+                albedo: solid_colour(Colour::new(0.05, 0.05, 0.1)),
+            })),
+            thickness: Arc::new(|_, _, p| 200.0 + 300.0 * p.y),
+            refractive_index: constant(1.8),
+        },
+        Material::Sheen {
+            base: Some(Box::new(Material::Lambertian {
+                albedo: solid_colour(Colour::new(0.25, 0.02, 0.05)),
+            })),
+            colour: solid_colour(Colour::new(1.0, 0.6, 0.7)),
+            roughness: constant(0.5),
+        },
+        Material::Sheen {
+            base: None,
+            colour: solid_colour(Colour::WHITE),
+            roughness: constant(0.8),
+        },
+    ];
+
+    for (i, material) in materials.into_iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-4.4 + 2.2 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material,
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// A screen showing an image, a warm spotlight from the ceiling that can't be seen from
+// above, and lights at different colour temperatures.
+pub fn emitters(t_min: f64, t_max: f64) -> Objects {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+
+    objects.push(
+        Box::new(
+            XYRect::new(
+                -3.0,
+                3.0,
+                0.5,
+                3.5,
+                -2.0,
+                Material::DiffuseLight {
+                    emit: image(Path::new("assets/earthmap.jpg")),
+                    intensity: 1.5,
+                    two_sided: false,
+                    spot: None,
+                    profile: None,
+                },
+            )
+        )
+    );
+
+    objects.push(
+        Box::new(
+            FlipNormals(
+                XZRect::new(
+                    3.0,
+                    4.0,
+                    1.0,
+                    2.0,
+                    6.0,
+                    Material::DiffuseLight {
+                        emit: solid_colour(blackbody(3000.0)),
+                        intensity: 40.0,
+                        two_sided: false,
+                        spot: Some(Spot {
+                            inner_angle: 10.0,
+                            outer_angle: 25.0,
+                        }),
+                        profile: None,
+                    },
+                )
+            )
+        )
+    );
+
+    for (i, temperature) in [1800.0, 4000.0, 10000.0].iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-3.0 + 1.2 * i as f64, 0.4, 2.0),
+                    radius: 0.4,
+                    material: Material::DiffuseLight {
+                        emit: solid_colour(blackbody(*temperature)),
+                        intensity: 1.0,
+                        two_sided: true,
+                        spot: None,
+                        profile: None,
+                    },
+                }
+            )
+        );
+    }
+
+    objects
+}
+
+// Diffuse, rough gold and principled spheres lit only by a point light, a spotlight and
+// a low sun, none of which can be seen directly.
+pub fn delta_lights(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+
+    let materials = vec![
+        Material::Lambertian {
+            albedo: solid_colour(Colour::new(0.8, 0.3, 0.3)),
+        },
+        Material::OrenNayar {
+            albedo: solid_colour(Colour::new(0.3, 0.8, 0.3)),
+            sigma: constant(0.8),
+        },
+        Material::Conductor {
+            eta: solid_colour(ComplexIor::GOLD.eta),
+            k: solid_colour(ComplexIor::GOLD.k),
+            roughness: constant(0.3),
+        },
+        Material::Principled(Box::new(Principled {
+            base_colour: solid_colour(Colour::new(0.2, 0.3, 0.8)),
+            roughness: constant(0.3),
+            clearcoat: constant(1.0),
+            clearcoat_gloss: constant(0.5),
+            ..Principled::default()
+        })),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-3.3 + 2.2 * i as f64, 1.0, 0.0),
+                    radius: 1.0,
+                    material,
+                }
+            )
+        );
+    }
+
+    let lights = vec![
+        Light::Point {
+            position: Pos3::new(-2.0, 4.0, 4.0),
+            intensity: Colour::new(30.0, 27.0, 24.0),
+            profile: None,
+        },
+        Light::Spot {
+            position: Pos3::new(3.0, 6.0, 2.0),
+            direction: Vec3::new(-0.3, -1.0, -0.3),
+            intensity: Colour::new(60.0, 70.0, 90.0),
+            spot: Spot {
+                inner_angle: 15.0,
+                outer_angle: 25.0,
+            },
+            profile: None,
+        },
+        Light::Directional {
+            direction: Vec3::new(1.0, -0.3, -0.5),
+            irradiance: Colour::new(0.6, 0.35, 0.15),
+        },
+    ];
+
+    (objects, lights)
+}
+
+// A wall lit by fixtures with measured distributions: a downlight as a point light, a
+// wall washer as a spotlight and the same downlight as a small emissive panel.
+pub fn ies_lights(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let downlight = Arc::new(IesProfile::load(Path::new("assets/ies/downlight.ies")).expect("Failed loading IES profile."));
+    let wall_washer = Arc::new(IesProfile::load(Path::new("assets/ies/wall_washer.ies")).expect("Failed loading IES profile."));
+
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+    objects.push(
+        Box::new(
+            XYRect::new(
+                -10.0,
+                10.0,
+                0.0,
+                8.0,
+                -3.0,
+                Material::Lambertian {
+                    albedo: solid_colour(Colour::new(0.75, 0.7, 0.65)),
+                },
+            )
+        )
+    );
+    objects.push(
+        Box::new(
+            FlipNormals(
+                XZRect::new(
+                    3.25,
+                    4.75,
+                    -2.75,
+                    -1.25,
+                    5.5,
+                    Material::DiffuseLight {
+                        emit: solid_colour(blackbody(3500.0)),
+                        intensity: 9.0,
+                        two_sided: false,
+                        spot: None,
+                        profile: Some(downlight.clone()),
+                    },
+                )
+            )
+        )
+    );
+
+    for (i, colour) in [Colour::new(0.8, 0.3, 0.3), Colour::new(0.3, 0.8, 0.3), Colour::new(0.3, 0.3, 0.8)].iter().enumerate() {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre: Pos3::new(-4.0 + 4.0 * i as f64, 0.7, 0.0),
+                    radius: 0.7,
+                    material: Material::Lambertian {
+                        albedo: solid_colour(*colour),
+                    },
+                }
+            )
+        );
+    }
+
+    let lights = vec![
+        Light::Point {
+            position: Pos3::new(-4.0, 5.5, -2.0),
+            intensity: 20.0 * blackbody(3500.0),
+            profile: Some(downlight),
+        },
+        Light::Spot {
+            position: Pos3::new(0.0, 5.5, -1.5),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            intensity: 20.0 * blackbody(5000.0),
+            spot: Spot {
+                inner_angle: 90.0,
+                outer_angle: 90.0,
+            },
+            profile: Some(wall_washer),
+        },
+    ];
+
+    (objects, lights)
+}
+
+// A cloud of hundreds of small bulbs at different colour temperatures around a few
+// spheres, all the light in the scene coming from the bulbs.
+pub fn many_lights(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+
+    let spheres = vec![
+        (
+            Pos3::new(-2.4, 1.0, 0.0),
+            Material::Lambertian {
+                albedo: solid_colour(Colour::new(0.8, 0.8, 0.8)),
+            },
+        ),
+        (
+            Pos3::new(0.0, 1.0, 0.0),
+            Material::OrenNayar {
+                albedo: solid_colour(Colour::new(0.3, 0.5, 0.8)),
+                sigma: constant(0.5),
+            },
+        ),
+        (
+            Pos3::new(2.4, 1.0, 0.0),
+            Material::Conductor {
+                eta: solid_colour(ComplexIor::COPPER.eta),
+                k: solid_colour(ComplexIor::COPPER.k),
+                roughness: constant(0.4),
+            },
+        ),
+    ];
+    let centres: Vec<Pos3> = spheres.iter().map(|(centre, _)| *centre).collect();
+    for (centre, material) in spheres {
+        objects.push(
+            Box::new(
+                Sphere {
+                    centre,
+                    radius: 1.0,
+                    material,
+                }
+            )
+        );
+    }
+
+    let num_bulbs = 300;
+    let mut lights = Vec::with_capacity(num_bulbs);
+    while lights.len() < num_bulbs {
+        let centre = Pos3::new(random_in_range(-6.0, 6.0), random_in_range(0.1, 4.0), random_in_range(-5.0, 3.0));
+        if centres.iter().any(|c| (centre - *c).length() < 1.2) {
+            continue;
+        }
+
+        lights.push(
+            Light::Sphere(
+                Sphere {
+                    centre,
+                    radius: 0.06,
+                    material: Material::DiffuseLight {
+                        emit: solid_colour(blackbody(random_in_range(1500.0, 8000.0))),
+                        intensity: 30.0,
+                        two_sided: true,
+                        spot: None,
+                        profile: None,
+                    },
+                }
+            )
+        );
+    }
+
+    (objects, lights)
+}
+
+// Fog scattering backwards, uniformly and forwards, from left to right, lit by a low sun
+// from behind so that the forward scattering fog glows the most.
+pub fn forward_scattering(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            }
+        )
+    );
+
+    let boundary = Material::Lambertian {
+        albedo: solid_colour(Colour::from(0.5)),
+    };
+    for (i, g) in [-0.7, 0.0, 0.7].iter().enumerate() {
+        let x = -3.1 + 3.1 * i as f64;
+        objects.push(
+            Box::new(
+                ConstantMedium::new(
+                    Cuboid::new(
+                        Pos3::new(x - 1.4, 0.0, -1.4),
+                        Pos3::new(x + 1.4, 2.8, 1.4),
+                        boundary.clone(),
+                    ),
+                    0.3,
+                    Material::Anisotropic {
+                        albedo: solid_colour(Colour::from(0.9)),
+                        phase: HenyeyGreenstein {
+                            g: *g,
+                        },
+                    },
+                )
+            )
+        );
+    }
+
+    let lights = vec![
+        Light::Directional {
+            direction: Vec3::new(0.0, -0.35, 1.0),
+            irradiance: Colour::new(3.0, 2.7, 2.3),
+        },
+    ];
+
+    (objects, lights)
+}
+
+pub fn clouds(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::new(0.45, 0.5, 0.4)),
+                },
+            }
+        )
+    );
+
+    let boundary = Material::Lambertian {
+        albedo: solid_colour(Colour::from(0.5)),
+    };
+
+    // A cloud from noise, breaking up towards the edge of its sphere.
+    let centre = Pos3::new(-1.9, 2.4, 0.0);
+    objects.push(
+        Box::new(
+            HeterogeneousMedium::new(
+                Sphere {
+                    centre,
+                    radius: 1.8,
+                    material: boundary.clone(),
+                },
+                ball(turbulence(Perlin::new(), 1.2, 16.0), centre, 1.8, 8.0),
+                32.0,
+                Material::Anisotropic {
+                    albedo: solid_colour(Colour::from(0.95)),
+                    phase: HenyeyGreenstein {
+                        g: 0.5,
+                    },
+                },
+            )
+        )
+    );
+
+    // A plume of smoke from a grid, widening and thinning as it rises and drifting to
+    // the side.
+    let perlin = Perlin::new();
+    let bounds = Aabb::new(Pos3::new(0.4, 0.0, -1.4), Pos3::new(3.6, 4.0, 1.4));
+    let plume = Grid::from_fn([32, 40, 28], bounds, |p| {
+        let axis = Pos3::new(1.4 + 0.15 * p.y * p.y, p.y, 0.0);
+        let radius = 0.2 + 0.25 * p.y;
+        let d = ((p.x - axis.x).powi(2) + (p.z - axis.z).powi(2)).sqrt() / radius;
+        if d >= 1.0 {
+            return 0.0;
+        }
+        let x = 1.0 - d;
+        let noise = 0.5 + 0.5 * perlin.turb(p * 2.5, 7);
+        4.0 * x * x * (3.0 - 2.0 * x) * (1.0 - 0.2 * p.y) * noise
+    });
+    let max_density = plume.max();
+    objects.push(
+        Box::new(
+            HeterogeneousMedium::new(
+                Cuboid::new(bounds.min, bounds.max, boundary),
+                grid(plume),
+                max_density,
+                Material::Isotropic {
+                    albedo: solid_colour(Colour::from(0.5)),
+                },
+            )
+        )
+    );
+
+    let lights = vec![
+        Light::Directional {
+            direction: Vec3::new(-1.0, -1.2, -0.6),
+            irradiance: Colour::new(3.0, 2.8, 2.5),
+        },
+    ];
+
+    (objects, lights)
+}
+
+// The same voxel plume three times, as dark smoke, dust and steam.
+pub fn voxel_smoke(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let plume = VoxelVolume::load(Path::new("assets/volumes/plume.vox")).expect("Failed loading voxel volume.");
+
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::new(0.5, 0.45, 0.4)),
+                },
+            }
+        )
+    );
+
+    let mut plumes: Objects = vec![];
+    for (i, albedo) in [Colour::from(0.2), Colour::new(0.7, 0.55, 0.4), Colour::from(0.95)].iter().enumerate() {
+        plumes.push(
+            Box::new(
+                Translate::new(
+                    RotateY::new(
+                        plume.medium(Material::Isotropic {
+                            albedo: solid_colour(*albedo),
+                        }),
+                        40.0 * i as f64,
+                    ),
+                    Vec3::new(-2.6 + 2.6 * i as f64, 0.0, -0.6 * i as f64),
+                )
+            )
+        );
+    }
+    objects.push(
+        Box::new(
+            Bvh::new(
+                plumes,
+                t_min,
+                t_max,
+            )
+        )
+    );
+
+    let lights = vec![
+        Light::Directional {
+            direction: Vec3::new(1.0, -1.0, -0.8),
+            irradiance: Colour::new(3.0, 2.8, 2.5),
+        },
+    ];
+
+    (objects, lights)
+}
+
+// Glowing media at night: the voxel plume burning at the temperatures stored with it, a
+// fireball from noise whose temperature follows its density, and a ball of plasma.
+pub fn fire(t_min: f64, t_max: f64) -> (Objects, Vec<Light>) {
+    let plume = VoxelVolume::load(Path::new("assets/volumes/plume.vox")).expect("Failed loading voxel volume.");
+
+    let mut objects: Objects = vec![];
+
+    objects.push(
+        Box::new(
+            Sphere {
+                centre: Pos3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+                material: Material::Lambertian {
+                    albedo: solid_colour(Colour::from(0.4)),
+                },
+            }
+        )
+    );
+
+    let temperature = plume.temperature_field().expect("Voxel volume has no temperatures.");
+    objects.push(
+        Box::new(
+            Translate::new(
+                plume.medium(Material::EmissiveMedium {
+                    base: Box::new(Material::Isotropic {
+                        albedo: solid_colour(Colour::from(0.3)),
+                    }),
+                    emission: incandescence(temperature, 1500.0, 15.0),
+                }),
+                Vec3::new(-2.4, 0.0, 0.0),
+            )
+        )
+    );
+
+    let centre = Pos3::new(0.6, 1.6, -0.4);
+    let density = ball(turbulence(Perlin::new(), 1.6, 8.0), centre, 1.3, 6.0);
+    let heat = density.clone();
+    objects.push(
+        Box::new(
+            HeterogeneousMedium::new(
+                Sphere {
+                    centre,
+                    radius: 1.3,
+                    material: Material::Lambertian {
+                        albedo: solid_colour(Colour::from(0.5)),
+                    },
+                },
+                density,
+                16.0,
+                Material::EmissiveMedium {
+                    base: Box::new(Material::Isotropic {
+                        albedo: solid_colour(Colour::from(0.5)),
+                    }),
+                    emission: incandescence(Arc::new(move |p| 900.0 + 120.0 * heat(p)), 1500.0, 3.0),
+                },
+            )
+        )
+    );
+
+    objects.push(
+        Box::new(
+            ConstantMedium::new(
+                Sphere {
+                    centre: Pos3::new(3.0, 0.8, 0.6),
+                    radius: 0.8,
+                    material: Material::Lambertian {
+                        albedo: solid_colour(Colour::from(0.5)),
+                    },
+                },
+                1.5,
+                Material::EmissiveMedium {
+                    base: Box::new(Material::Isotropic {
+                        albedo: solid_colour(Colour::from(0.8)),
+                    }),
+                    emission: glow(Colour::new(0.15, 0.3, 0.8), uniform(1.0)),
+                },
+            )
+        )
+    );
+
+    let lights = vec![
+        Light::Directional {
+            direction: Vec3::new(0.5, -1.0, -0.5),
+            irradiance: Colour::new(0.1, 0.12, 0.2),
+        },
+    ];
+
+    (objects, lights)
+}
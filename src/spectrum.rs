@@ -0,0 +1,136 @@
+// Single wavelengths in an otherwise RGB renderer.
+//
+// Paths are RGB until they hit something whose behaviour depends on the wavelength, like
+// a dispersive dielectric. There a wavelength is sampled and kept by the ray for the rest
+// of the path, with the throughput weighted by the RGB colour of that wavelength. This is
+// plain single wavelength sampling, one uniformly chosen wavelength per path, so colour
+// noise behind dispersive objects only goes down with more samples per pixel.
+
+use crate::colour::*;
+use crate::utility::*;
+
+use std::sync::OnceLock;
+
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 730.0;
+
+// One sided Gaussian lobes, "Simple Analytic Approximations to the CIE XYZ Color Matching
+// Functions", Wyman et al. 2013.
+fn lobe(wavelength: f64, mean: f64, sigma_below: f64, sigma_above: f64) -> f64 {
+    let sigma = if wavelength < mean { sigma_below } else { sigma_above };
+    let t = (wavelength - mean) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+// The CIE 1931 colour matching functions, wavelength in nanometres.
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let l = wavelength;
+    let x = 1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7) - 0.065 * lobe(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+// Linear sRGB, with the out of gamut negative parts of spectral colours clipped.
+fn wavelength_to_rgb(wavelength: f64) -> Colour {
+    let (x, y, z) = cie_xyz(wavelength);
+    Colour::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    ).map(|c| c.max(0.0))
+}
+
+// The RGB colours of all wavelengths, scaled so that they average to white over the
+// range of wavelengths, i.e. so that a path that samples its wavelength uniformly keeps
+// its RGB throughput on average.
+fn normalisation() -> Colour {
+    static NORMALISATION: OnceLock<Colour> = OnceLock::new();
+
+    *NORMALISATION.get_or_init(|| {
+        let steps = 1000;
+        let sum: Colour = (0..steps)
+            .map(|i| {
+                let wavelength = MIN_WAVELENGTH + (i as f64 + 0.5) / steps as f64 * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+                wavelength_to_rgb(wavelength)
+            })
+            .sum();
+        sum / steps as f64
+    })
+}
+
+// Samples a wavelength uniformly, along with the weight that turns an RGB throughput
+// into one for that wavelength alone.
+pub fn sample_wavelength() -> (f64, Colour) {
+    let wavelength = random_in_range(MIN_WAVELENGTH, MAX_WAVELENGTH);
+    let norm = normalisation();
+    let rgb = wavelength_to_rgb(wavelength);
+
+    (wavelength, Colour::new(rgb.r / norm.r, rgb.g / norm.g, rgb.b / norm.b))
+}
+
+// The RGB colour reflected under white light by a surface whose reflectance at each
+// wavelength is given by f, e.g. for interference effects.
+pub fn reflectance_to_rgb(f: impl Fn(f64) -> f64) -> Colour {
+    let steps = 32;
+    let sum: Colour = (0..steps)
+        .map(|i| {
+            let wavelength = MIN_WAVELENGTH + (i as f64 + 0.5) / steps as f64 * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+            f(wavelength) * wavelength_to_rgb(wavelength)
+        })
+        .sum();
+    let norm = normalisation();
+    let rgb = sum / steps as f64;
+
+    Colour::new(rgb.r / norm.r, rgb.g / norm.g, rgb.b / norm.b)
+}
+
+// The radiance of a black body at a temperature in kelvin, up to a constant factor, so
+// only meaningful relative to that at another temperature.
+pub fn blackbody_radiance(temperature: f64) -> Colour {
+    // Planck's law, constants in SI units.
+    let (h, c, k) = (6.626_070_15e-34, 299_792_458.0, 1.380_649e-23);
+    let radiance = |wavelength: f64| {
+        let l = wavelength * 1e-9;
+        2.0 * h * c * c / (l.powi(5) * ((h * c / (l * k * temperature)).exp() - 1.0))
+    };
+
+    reflectance_to_rgb(radiance)
+}
+
+// The colour of a black body at a temperature in kelvin, scaled to a luminance of 1 so
+// that the brightness can be set separately, e.g. by the intensity of a light.
+pub fn blackbody(temperature: f64) -> Colour {
+    let rgb = blackbody_radiance(temperature);
+    let luminance = rgb.luminance();
+    if luminance > 0.0 && luminance.is_finite() {
+        rgb / luminance
+    } else {
+        Colour::BLACK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_wavelengths_average_to_white() {
+        seed_rng(9);
+        let n = 200_000;
+        let mean = (0..n).map(|_| sample_wavelength().1).sum::<Colour>() / n as f64;
+        assert!(mean.all(|c| (c - 1.0).abs() < 0.01), "{:?}", mean);
+    }
+
+    #[test]
+    fn blackbodies_have_unit_luminance() {
+        for &temperature in &[1000.0, 2700.0, 5500.0, 6500.0, 10_000.0, 30_000.0] {
+            let colour = blackbody(temperature);
+            assert!((colour.luminance() - 1.0).abs() < 1e-9, "{:?} at {} K", colour, temperature);
+        }
+
+        // Warm then cool.
+        let (warm, cool) = (blackbody(2700.0), blackbody(10_000.0));
+        assert!(warm.r > warm.b && cool.b > cool.r);
+    }
+}
@@ -10,6 +10,25 @@ use ::image::*;
 
 pub type Texture = Arc<dyn Fn(f64, f64, Vec3) -> Colour + Send + Sync>;
 
+// A texture of a single value, e.g. a roughness.
+pub type ScalarTexture = Arc<dyn Fn(f64, f64, Vec3) -> f64 + Send + Sync>;
+
+pub fn constant(x: f64) -> ScalarTexture {
+    Arc::new(
+        move |_, _, _| x
+    )
+}
+
+// The average of the channels of a texture, e.g. to use a greyscale image as a roughness map.
+pub fn grey(texture: Texture) -> ScalarTexture {
+    Arc::new(
+        move |u, v, p| {
+            let col = texture(u, v, p);
+            (col.r + col.g + col.b) / 3.0
+        }
+    )
+}
+
 pub fn solid_colour(col: Colour) -> Texture {
     Arc::new(
         move |_, _, _| col
@@ -38,39 +57,38 @@ pub fn noise(noise: Perlin, scale: f64) -> Texture {
     )
 }
 
+// Values are used as they are stored, without gamma decoding, so that the same loader
+// works for data such as normal maps.
 pub fn image(image: &Path) -> Texture {
     let image = image::open(image);
 
     if let Ok(image) = image {
+        let image = image.to_rgb();
 
-        if let DynamicImage::ImageRgb8(image) = image {
-            Arc::new(
-                move |u, v, _| {
-                    let (width, height) = &image.dimensions();
-                    let width = *width;
-                    let height = *height;
+        Arc::new(
+            move |u, v, _| {
+                let (width, height) = &image.dimensions();
+                let width = *width;
+                let height = *height;
 
-                    let u = clamp(0.0, 1.0, u);
-                    let v = 1.0 - clamp(0.0, 1.0, v);
+                let u = clamp(0.0, 1.0, u);
+                let v = 1.0 - clamp(0.0, 1.0, v);
 
-                    let mut x = (u * width as f64).floor() as u32;
-                    let mut y = (v * height as f64).floor() as u32;
+                let mut x = (u * width as f64).floor() as u32;
+                let mut y = (v * height as f64).floor() as u32;
 
-                    if x >= width {
-                        x = width - 1;
-                    }
-                    if y >= height {
-                        y = height - 1;
-                    }
+                if x >= width {
+                    x = width - 1;
+                }
+                if y >= height {
+                    y = height - 1;
+                }
 
-                    let pixel = image.get_pixel(x, y).channels();
+                let pixel = image.get_pixel(x, y).channels();
 
-                    Colour::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64).map(|c| c / 255.0)
-                }
-            )
-        } else {
-            panic!("Got weird image when opening texture");
-        }
+                Colour::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64).map(|c| c / 255.0)
+            }
+        )
     } else {
         eprintln!("Failed opening image for a texture!");
         Arc::new(
@@ -1,7 +1,37 @@
 use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use std::cell::RefCell;
 
 pub const PI: f64 = std::f64::consts::PI;
-pub const INF: f64 = std::f64::INFINITY;
+pub const INF: f64 = f64::INFINITY;
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Reseeds the random number generator of the current thread, everything random
+// (scene construction as well as sampling) goes through this generator.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+// Combines a seed with a number of indices (pixel coordinates, sample index etc.),
+// giving well-spread seeds for neighbouring indices.
+pub fn hash_seed(seed: u64, indices: &[u64]) -> u64 {
+    indices.iter().fold(seed, |acc, &i| {
+        let mut h = acc ^ i.wrapping_add(0x9e37_79b9_7f4a_7c15).wrapping_add(acc << 6).wrapping_add(acc >> 2);
+        h ^= h >> 30;
+        h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h ^= h >> 27;
+        h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^ (h >> 31)
+    })
+}
 
 pub fn deg_to_rad(deg: f64) -> f64 {
     (deg  / 360.0) * 2.0 * PI
@@ -11,7 +41,7 @@ pub fn clamp(min: f64, max: f64, x: f64) -> f64 {
     if x.is_nan() {
         return x;
     }
-    
+
     if x < min {
         min
     } else if x > max {
@@ -22,11 +52,11 @@ pub fn clamp(min: f64, max: f64, x: f64) -> f64 {
 }
 
 pub fn random_zero_one() -> f64 {
-    random()
+    with_rng(|rng| rng.gen())
 }
 
 pub fn random_in_range(start: f64, end: f64) -> f64 {
-    thread_rng().gen_range(start, end)
+    with_rng(|rng| rng.gen_range(start, end))
 }
 
 pub fn min(a: f64, b: f64) -> f64 {
@@ -37,4 +67,4 @@ pub fn schlick(cos: f64, ref_idx: f64) -> f64 {
     let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
     let r0 = r0 * r0;
     r0 + (1.0 - r0) * (1.0 - cos).powi(5)
-}
\ No newline at end of file
+}
@@ -1,8 +1,8 @@
 use std::ops::{Add, AddAssign, Neg, Sub, SubAssign, Mul, MulAssign, Div, DivAssign, Index, IndexMut};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use crate::utility::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -140,15 +140,13 @@ impl IndexMut<usize> for Vec3 {
 }
 
 pub fn random_vec() -> Vec3 {
-    let mut rng = thread_rng();
-    
-    Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0))
+    with_rng(|rng|
+        Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0))
+    )
 }
 
 pub fn random_unit_vec() -> Vec3 {
-    let mut rng = thread_rng();
-    let a: f64 = rng.gen_range(0.0, 2.0 * PI);
-    let z: f64 = rng.gen_range(-1.0, 1.0);
+    let (a, z): (f64, f64) = with_rng(|rng| (rng.gen_range(0.0, 2.0 * PI), rng.gen_range(-1.0, 1.0)));
     let r: f64 = (1.0 - z * z).sqrt();
     Vec3::new(
         r * a.cos(),
@@ -183,6 +181,15 @@ pub fn random_vec_in_unit_disk() -> Vec3 {
     }
 }
 
+// A direction in the hemisphere around +z, distributed with the cosine of its angle to +z.
+pub fn random_cosine_direction() -> Vec3 {
+    let (u1, u2) = (random_zero_one(), random_zero_one());
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u1).sqrt())
+}
+
 pub fn random_vec_in_hemisphere(normal: &Vec3) -> Vec3 {
     let vec = random_vec_in_unit_sphere();
     if Vec3::dot(normal, &vec) > 0.0 {
@@ -0,0 +1,38 @@
+// Renders through the binary with worker processes, one of which dies straight away,
+// and checks that the image matches a local render.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn render(output: &Path, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_ray_tracing"))
+        .args(["--scene", "three_different_objects", "--width", "24", "--height", "16"])
+        .args(["--samples", "4", "--samples-per-pass", "2", "--bounces", "4", "--tile-size", "8", "--seed", "7"])
+        .arg("--output")
+        .arg(output)
+        .args(args)
+        .status()
+        .expect("Failed running the renderer.");
+    assert!(status.success(), "render with {:?} failed", args);
+}
+
+fn output(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+#[test]
+fn workers_match_a_local_render() {
+    let (local, distributed) = (output("local.png"), output("distributed.png"));
+    render(&local, &[]);
+    render(&distributed, &["--remote-worker", "false", "--workers", "2"]);
+
+    let local = image::open(&local).unwrap().to_rgb();
+    let distributed = image::open(&distributed).unwrap().to_rgb();
+    assert_eq!(local.dimensions(), distributed.dimensions());
+    // Up to rounding, the samples of a pixel being added up in a different order.
+    for (a, b) in local.pixels().zip(distributed.pixels()) {
+        for c in 0..3 {
+            assert!((a[c] as i32 - b[c] as i32).abs() <= 1, "{:?} against {:?}", a, b);
+        }
+    }
+}